@@ -0,0 +1,244 @@
+use std::io::{Read, Seek, SeekFrom};
+use std::time::Duration;
+
+#[derive(Clone)]
+pub struct Chapter {
+    pub title: String,
+    pub start: Duration,
+}
+
+/// Reads the chapters embedded in a file: ID3 `CHAP` frames for MP3s, and chapter tracks or
+/// Nero `chpl` atoms for MP4s (M4B/M4A). Files without chapters give an empty list.
+pub fn read(path: &str) -> anyhow::Result<Vec<Chapter>> {
+    let extension = path.rsplit('.').next().unwrap_or("").to_ascii_lowercase();
+    let mut chapters = match extension.as_str() {
+        "mp3" => read_id3(path)?,
+        "m4a" | "m4b" | "mp4" => read_mp4(path)?,
+        _ => Vec::new(),
+    };
+    chapters.sort_by_key(|x| x.start);
+    Ok(chapters)
+}
+
+fn read_id3(path: &str) -> anyhow::Result<Vec<Chapter>> {
+    use id3::TagLike;
+    let tag = match id3::Tag::read_from_path(path) {
+        Ok(x) => x,
+        Err(e) if matches!(e.kind, id3::ErrorKind::NoTag) => return Ok(Vec::new()),
+        Err(e) => return Err(e.into()),
+    };
+    Ok(tag
+        .chapters()
+        .map(|x| Chapter {
+            title: x.title().unwrap_or(&x.element_id).to_owned(),
+            start: Duration::from_millis(x.start_time as u64),
+        })
+        .collect())
+}
+
+/// Iterates over the boxes (atoms) in `data` as `(type, body)`.
+fn boxes(mut data: &[u8]) -> impl Iterator<Item = (&[u8], &[u8])> {
+    std::iter::from_fn(move || {
+        let size = u32::from_be_bytes(data.get(0..4)?.try_into().unwrap()) as usize;
+        let name = data.get(4..8)?;
+        let (header, size) = match size {
+            0 => (8, data.len()),
+            1 => (
+                16,
+                u64::from_be_bytes(data.get(8..16)?.try_into().unwrap()) as usize,
+            ),
+            x => (8, x),
+        };
+        let body = data.get(header..size)?;
+        let item = (name, body);
+        data = &data[size..];
+        Some(item)
+    })
+}
+
+fn child<'a>(data: &'a [u8], path: &[&[u8; 4]]) -> Option<&'a [u8]> {
+    path.iter().try_fold(data, |data, name| {
+        boxes(data).find(|(x, _)| x == name).map(|(_, x)| x)
+    })
+}
+
+fn be_u32(data: &[u8], offset: usize) -> Option<u32> {
+    Some(u32::from_be_bytes(
+        data.get(offset..offset + 4)?.try_into().unwrap(),
+    ))
+}
+
+fn be_u64(data: &[u8], offset: usize) -> Option<u64> {
+    Some(u64::from_be_bytes(
+        data.get(offset..offset + 8)?.try_into().unwrap(),
+    ))
+}
+
+/// Reads the `u32`s of a table box such as `stco`, after its version, flags and entry count.
+fn table(data: &[u8], entry_len: usize) -> impl Iterator<Item = u32> + '_ {
+    let count = be_u32(data, 4).unwrap_or(0) as usize;
+    (0..count * entry_len).map_while(move |i| be_u32(data, 8 + i * 4))
+}
+
+fn read_mp4(path: &str) -> anyhow::Result<Vec<Chapter>> {
+    let mut file = std::fs::File::open(path)?;
+    let file_len = file.metadata()?.len();
+    // Only `moov` is read into memory, the media data may be huge
+    let moov = loop {
+        let mut header = [0u8; 8];
+        if file.read_exact(&mut header).is_err() {
+            return Ok(Vec::new());
+        }
+        let (header_len, size) = match u32::from_be_bytes(header[0..4].try_into().unwrap()) {
+            1 => {
+                let mut size = [0u8; 8];
+                file.read_exact(&mut size)?;
+                (16, u64::from_be_bytes(size))
+            }
+            0 => return Ok(Vec::new()),
+            x => (8, x as u64),
+        };
+        // Sizes are checked not to allocate more than the file could hold
+        if size < header_len
+            || file
+                .stream_position()?
+                .checked_add(size - header_len)
+                .is_none_or(|x| x > file_len)
+        {
+            return Ok(Vec::new());
+        }
+        if &header[4..8] == b"moov" {
+            let mut moov = vec![0u8; (size - header_len) as usize];
+            file.read_exact(&mut moov)?;
+            break moov;
+        }
+        file.seek(SeekFrom::Current((size - header_len) as i64))?;
+    };
+    match read_chapter_track(&moov, &mut file)? {
+        Some(x) if !x.is_empty() => Ok(x),
+        _ => Ok(read_chpl(&moov).unwrap_or_default()),
+    }
+}
+
+/// Reads the QuickTime chapter track, the text track referred to by another track's `chap`.
+fn read_chapter_track(
+    moov: &[u8],
+    file: &mut std::fs::File,
+) -> anyhow::Result<Option<Vec<Chapter>>> {
+    let traks = boxes(moov)
+        .filter(|(x, _)| *x == b"trak")
+        .map(|(_, x)| x)
+        .collect::<Vec<_>>();
+    let Some(chapter_id) = traks
+        .iter()
+        .find_map(|x| child(x, &[b"tref", b"chap"]).and_then(|x| be_u32(x, 0)))
+    else {
+        return Ok(None);
+    };
+    let Some(trak) = traks.iter().find(|x| {
+        child(x, &[b"tkhd"]).and_then(|x| match x.first() {
+            Some(1) => be_u32(x, 20),
+            _ => be_u32(x, 12),
+        }) == Some(chapter_id)
+    }) else {
+        return Ok(None);
+    };
+    let Some(timescale) = child(trak, &[b"mdia", b"mdhd"]).and_then(|x| match x.first() {
+        Some(1) => be_u32(x, 20),
+        _ => be_u32(x, 12),
+    }) else {
+        return Ok(None);
+    };
+    let Some(stbl) = child(trak, &[b"mdia", b"minf", b"stbl"]) else {
+        return Ok(None);
+    };
+
+    let mut starts = Vec::new();
+    let mut time = 0u64;
+    let stts = table(child(stbl, &[b"stts"]).unwrap_or_default(), 2).collect::<Vec<_>>();
+    for x in stts.chunks_exact(2) {
+        // At most one chapter per byte of `moov`, however many samples a corrupt table claims
+        for _ in 0..(x[0] as usize).min(moov.len().saturating_sub(starts.len())) {
+            starts.push(time);
+            time += x[1] as u64;
+        }
+    }
+
+    let stsz = child(stbl, &[b"stsz"]).unwrap_or_default();
+    let sizes = match be_u32(stsz, 4) {
+        Some(0) => (0..be_u32(stsz, 8).unwrap_or(0) as usize)
+            .map_while(|i| be_u32(stsz, 12 + i * 4))
+            .collect::<Vec<_>>(),
+        Some(x) => vec![x; starts.len()],
+        None => return Ok(None),
+    };
+
+    let chunk_offsets = match (child(stbl, &[b"stco"]), child(stbl, &[b"co64"])) {
+        (Some(x), _) => table(x, 1).map(|x| x as u64).collect::<Vec<_>>(),
+        (None, Some(x)) => (0..be_u32(x, 4).unwrap_or(0) as usize)
+            .map_while(|i| be_u64(x, 8 + i * 8))
+            .collect(),
+        (None, None) => return Ok(None),
+    };
+    let stsc = table(child(stbl, &[b"stsc"]).unwrap_or_default(), 3).collect::<Vec<_>>();
+    let mut offsets = Vec::new();
+    for (i, offset) in chunk_offsets.iter().enumerate() {
+        let samples_per_chunk = stsc
+            .chunks_exact(3)
+            .take_while(|x| x[0] as usize <= i + 1)
+            .last()
+            .map_or(1, |x| x[1]);
+        let mut offset = *offset;
+        for _ in 0..samples_per_chunk {
+            let Some(size) = sizes.get(offsets.len()) else {
+                break;
+            };
+            offsets.push(offset);
+            offset += *size as u64;
+        }
+    }
+
+    let mut chapters = Vec::new();
+    for (start, offset) in starts.into_iter().zip(offsets) {
+        // Each sample is a big-endian u16 length followed by the text
+        file.seek(SeekFrom::Start(offset))?;
+        let mut len = [0u8; 2];
+        file.read_exact(&mut len)?;
+        let mut title = vec![0u8; u16::from_be_bytes(len) as usize];
+        file.read_exact(&mut title)?;
+        // Skipped if out of range, as in a corrupt file
+        let Ok(start) = Duration::try_from_secs_f64(start as f64 / timescale.max(1) as f64) else {
+            continue;
+        };
+        chapters.push(Chapter {
+            title: String::from_utf8_lossy(&title).into_owned(),
+            start,
+        });
+    }
+    Ok(Some(chapters))
+}
+
+/// Reads Nero chapters from `moov/udta/chpl`.
+fn read_chpl(moov: &[u8]) -> Option<Vec<Chapter>> {
+    let chpl = child(moov, &[b"udta", b"chpl"])?;
+    let mut offset = if *chpl.first()? == 0 { 4 } else { 8 };
+    let count = *chpl.get(offset)?;
+    offset += 1;
+    let mut chapters = Vec::new();
+    for _ in 0..count {
+        // Timestamps are in units of 100 nanoseconds
+        let start = be_u64(chpl, offset)?;
+        let len = *chpl.get(offset + 8)? as usize;
+        let title = chpl.get(offset + 9..offset + 9 + len)?;
+        offset += 9 + len;
+        // Skipped if out of range, as in a corrupt file
+        let Some(start) = start.checked_mul(100) else {
+            continue;
+        };
+        chapters.push(Chapter {
+            title: String::from_utf8_lossy(title).into_owned(),
+            start: Duration::from_nanos(start),
+        });
+    }
+    Some(chapters)
+}
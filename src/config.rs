@@ -1,14 +1,113 @@
+use crate::listenbrainz::Server;
+use crate::smart::{Order, SmartPlaylist};
 use crate::song::Source;
+use crate::ui::Column;
 
 pub const FOCUSED_FRAME_DELAY: f64 = 1f64 / 10f64; // 10fps
 pub const UNFOCUSED_FRAME_DELAY: f64 = 1f64; // 1fps
 
+pub const SLEEP_TIMER_MINUTES: [u64; 4] = [15, 30, 45, 60];
+pub const SLEEP_TIMER_FADE_SECS: u64 = 30;
+
+// Songs longer than this remember where they were left off
+pub const RESUME_THRESHOLD_SECS: u64 = 20 * 60;
+
+// Whether ratings are also written to the files' tags (`POPM` in MP3s, `FMPS_RATING` in FLACs)
+pub const WRITE_RATING_TAGS: bool = false;
+
+pub const SESSION_SAVE_INTERVAL_SECS: u64 = 30;
+
+// A song counts as played once this much of it, or this many seconds, have been played, and as
+// skipped if it is skipped before that
+pub const PLAY_THRESHOLD_PERCENT: u64 = 50;
+pub const PLAY_THRESHOLD_SECS: u64 = 4 * 60;
+
+// How many past searches are remembered
+pub const SEARCH_HISTORY_LEN: usize = 100;
+
+// How many of the best fuzzy matches are listed while typing a search
+pub const FINDER_RESULTS: usize = 10;
+
+// Columns of the library with their widths. `Title` takes up the remaining width, at least its own.
+// Columns changed in the library are saved in `settings.json` in `config_dir` and take precedence.
+pub const LIBRARY_COLUMNS: [(Column, u16); 3] = [
+    (Column::Index, 5),
+    (Column::Title, 20),
+    (Column::Duration, 8),
+];
+
+// Smart playlists, shown after the other sources in the library. Their queries are search queries,
+// see the README.
+pub const SMART_PLAYLISTS: &[SmartPlaylist] = &[
+    SmartPlaylist {
+        title: "Added this week",
+        query: "added:..7",
+        order: Order::Newest,
+        limit: None,
+    },
+    SmartPlaylist {
+        title: "Random jazz",
+        query: "genre:jazz",
+        order: Order::Random,
+        limit: Some(50),
+    },
+    SmartPlaylist {
+        title: "The sixties",
+        query: "year:1960..1969",
+        order: Order::Library,
+        limit: None,
+    },
+];
+
+pub fn data_dir() -> std::path::PathBuf {
+    dirs::data_dir().unwrap_or_default().join("mmmmmusic")
+}
+
+// Where configuration changed from the ui is saved, e.g. the columns of the library
+pub fn config_dir() -> std::path::PathBuf {
+    dirs::config_dir().unwrap_or_default().join("mmmmmusic")
+}
+
+// Whether the player shows peak and RMS level meters of each channel
+pub const SHOW_LEVEL_METERS: bool = true;
+
+// Height of the spectrum analyzer/oscilloscope below the library, in rows
+pub const VISUALIZER_HEIGHT: u16 = 12;
+
+// Whether the album art of the playing song is shown above the playlist, and the image files
+// looked for next to songs without embedded art, in order of preference (ignoring case)
+pub const SHOW_COVER: bool = true;
+pub const COVER_FILES: &[&str] = &[
+    "cover.jpg",
+    "cover.png",
+    "folder.jpg",
+    "folder.png",
+    "front.jpg",
+    "front.png",
+];
+// Covers larger than this many pixels wide or high are downscaled as soon as they are loaded
+pub const COVER_MAX_SIZE: u32 = 1024;
+
+// Where songs are logged in the `.scrobbler.log` format as they finish playing, for tools that
+// submit them later. `None` to not log them.
+pub fn scrobbler_log() -> Option<std::path::PathBuf> {
+    Some(data_dir().join(".scrobbler.log"))
+}
+
+// A ListenBrainz-compatible server to submit the playing song and listens to, e.g.
+// `Some(Server { url: "https://api.listenbrainz.org", token: "<your user token>" })`, or the
+// ListenBrainz API of a self-hosted Maloja (`https://<host>/apis/listenbrainz`) or Koito. Listens
+// that couldn't be submitted are retried every `SCROBBLE_RETRY_SECS`.
+pub const LISTENBRAINZ: Option<Server> = None;
+pub const SCROBBLE_RETRY_SECS: u64 = 60;
+
 pub fn sources(library: &mut crate::ui::Library) -> anyhow::Result<()> {
     library.add_source(Source::from_file(
         Some("Example(file)".to_owned()),
         "/example/file.mp3".to_owned(),
     )?);
-    // Ensure that the directory only contains music files and .DS_Store(will be ignored)
+    // Ensure that the directory only contains music files, playlists, CUE sheets
+    // and .DS_Store(will be ignored). Files described by a CUE sheet are split into its tracks.
     library.add_source(Source::from_directory(
         Some("Example(directory)".to_owned()),
         "/example/dir".to_owned(),
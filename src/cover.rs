@@ -0,0 +1,39 @@
+use crate::config::{COVER_FILES, COVER_MAX_SIZE};
+use crate::song::Song;
+use image::DynamicImage;
+use std::path::Path;
+
+/// Reads the album art of a song: the picture embedded in its tags, or else an image file such as
+/// `cover.jpg` in its directory (see `COVER_FILES`), downscaled to `COVER_MAX_SIZE`. Decoding can
+/// take a while, so this is meant to run outside of the UI thread.
+pub fn load(song: &Song) -> Option<DynamicImage> {
+    let image = read(song)?;
+    if image.width() > COVER_MAX_SIZE || image.height() > COVER_MAX_SIZE {
+        Some(image.thumbnail(COVER_MAX_SIZE, COVER_MAX_SIZE))
+    } else {
+        Some(image)
+    }
+}
+
+fn read(song: &Song) -> Option<DynamicImage> {
+    let embedded = audiotags::Tag::new()
+        .read_from_path(song.path())
+        .ok()
+        .and_then(|x| image::load_from_memory(x.album_cover()?.data).ok());
+    embedded.or_else(|| {
+        let dir = Path::new(song.path()).parent()?;
+        let files = std::fs::read_dir(dir)
+            .ok()?
+            .filter_map(|x| x.ok())
+            .map(|x| x.path())
+            .collect::<Vec<_>>();
+        // The first of `COVER_FILES` found, ignoring case
+        COVER_FILES.iter().find_map(|name| {
+            let path = files.iter().find(|x| {
+                x.file_name()
+                    .is_some_and(|x| x.to_string_lossy().eq_ignore_ascii_case(name))
+            })?;
+            image::open(path).ok()
+        })
+    })
+}
@@ -0,0 +1,114 @@
+use std::collections::HashMap;
+use std::path::Path;
+use std::time::Duration;
+
+#[derive(Default)]
+pub struct CueSheet {
+    pub title: Option<String>,
+    pub performer: Option<String>,
+    /// `REM` comments of the whole sheet, e.g. `GENRE` or `DATE`.
+    pub rem: HashMap<String, String>,
+    pub files: Vec<CueFile>,
+}
+
+pub struct CueFile {
+    pub path: String,
+    pub tracks: Vec<CueTrack>,
+}
+
+#[derive(Default)]
+pub struct CueTrack {
+    pub number: Option<u16>,
+    pub title: Option<String>,
+    pub performer: Option<String>,
+    /// `INDEX 01` of the track.
+    pub start: Duration,
+    /// `INDEX 01` of the next track in the same file, if any.
+    pub end: Option<Duration>,
+}
+
+/// Splits a line into words, keeping quoted strings together.
+fn words(line: &str) -> Vec<String> {
+    let mut words = Vec::new();
+    let mut chars = line.trim().chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            c if c.is_whitespace() => {}
+            '"' => words.push(chars.by_ref().take_while(|x| *x != '"').collect()),
+            c => {
+                let mut word = c.to_string();
+                while let Some(c) = chars.next_if(|x| !x.is_whitespace()) {
+                    word.push(c);
+                }
+                words.push(word);
+            }
+        }
+    }
+    words
+}
+
+/// Parses `mm:ss:ff`, where there are 75 frames in a second.
+fn parse_time(time: &str) -> Option<Duration> {
+    let mut parts = time.split(':').map(|x| x.parse::<u64>().ok());
+    let (m, s, f) = (parts.next()??, parts.next()??, parts.next()??);
+    Some(Duration::from_millis((m * 60 + s) * 1000 + f * 1000 / 75))
+}
+
+/// Reads a CUE sheet. `FILE` paths are resolved against the sheet's directory.
+/// Whether a file is a CUE sheet, going by its extension in any case, e.g. `Album.CUE`.
+pub fn is_cue(path: &Path) -> bool {
+    path.extension()
+        .is_some_and(|x| x.eq_ignore_ascii_case("cue"))
+}
+
+pub fn read(path: &Path) -> anyhow::Result<CueSheet> {
+    let content = String::from_utf8_lossy(&std::fs::read(path)?).into_owned();
+    let base = path.parent().unwrap_or(Path::new(""));
+    let mut sheet = CueSheet::default();
+    for line in content.lines() {
+        let line = line.trim_start_matches('\u{feff}');
+        let words = words(line);
+        let Some(command) = words.first() else {
+            continue;
+        };
+        let arg = words.get(1).cloned();
+        let track = sheet.files.last_mut().and_then(|x| x.tracks.last_mut());
+        match (command.to_ascii_uppercase().as_str(), track) {
+            ("FILE", _) => sheet.files.push(CueFile {
+                path: crate::playlists::resolve(base, &arg.unwrap_or_default()),
+                tracks: Vec::new(),
+            }),
+            ("TRACK", _) => {
+                let Some(file) = sheet.files.last_mut() else {
+                    anyhow::bail!("TRACK before FILE in {}", path.display());
+                };
+                file.tracks.push(CueTrack {
+                    number: arg.and_then(|x| x.parse().ok()),
+                    ..Default::default()
+                });
+            }
+            ("TITLE", Some(track)) => track.title = arg,
+            ("TITLE", None) => sheet.title = arg,
+            ("PERFORMER", Some(track)) => track.performer = arg,
+            ("PERFORMER", None) => sheet.performer = arg,
+            ("INDEX", Some(track)) if arg.as_deref() == Some("01") => {
+                track.start = words.get(2).and_then(|x| parse_time(x)).unwrap_or_default();
+            }
+            ("REM", None) => {
+                if let Some(key) = arg {
+                    sheet
+                        .rem
+                        .insert(key.to_ascii_uppercase(), words[2..].join(" "));
+                }
+            }
+            _ => {}
+        }
+    }
+    for file in sheet.files.iter_mut() {
+        let starts = file.tracks.iter().map(|x| x.start).collect::<Vec<_>>();
+        for (track, end) in file.tracks.iter_mut().zip(starts.into_iter().skip(1)) {
+            track.end = Some(end);
+        }
+    }
+    Ok(sheet)
+}
@@ -0,0 +1,395 @@
+use crate::config::{data_dir, SCROBBLE_RETRY_SECS};
+use crate::song::Song;
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{Receiver, RecvTimeoutError, Sender};
+use std::time::Duration;
+
+/// A server speaking the ListenBrainz API: ListenBrainz itself, or a self-hosted one such as
+/// Maloja or Koito.
+pub struct Server {
+    /// The root of the API, e.g. `https://api.listenbrainz.org`.
+    pub url: &'static str,
+    /// The user token, sent as `Authorization: Token <token>`.
+    pub token: &'static str,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+struct AdditionalInfo {
+    duration_ms: u64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    tracknumber: Option<u16>,
+    media_player: String,
+    submission_client: String,
+    submission_client_version: String,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+struct TrackMetadata {
+    artist_name: String,
+    track_name: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    release_name: Option<String>,
+    additional_info: AdditionalInfo,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+struct Listen {
+    /// Seconds since the Unix epoch, absent for the song playing now.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    listened_at: Option<u64>,
+    track_metadata: TrackMetadata,
+}
+
+impl Listen {
+    fn new(song: &Song, listened_at: Option<u64>) -> Self {
+        Self {
+            listened_at,
+            track_metadata: TrackMetadata {
+                artist_name: song.get_artist().unwrap_or("").to_owned(),
+                track_name: song.get_title().unwrap_or("").to_owned(),
+                release_name: song
+                    .get_album()
+                    .ok()
+                    .filter(|x| !x.is_empty())
+                    .map(str::to_owned),
+                additional_info: AdditionalInfo {
+                    duration_ms: song.get_duration().unwrap_or_default().as_millis() as u64,
+                    tracknumber: song.get_track().ok().flatten(),
+                    media_player: "mmmmmusic".to_owned(),
+                    submission_client: "mmmmmusic".to_owned(),
+                    submission_client_version: env!("CARGO_PKG_VERSION").to_owned(),
+                },
+            },
+        }
+    }
+}
+
+enum Message {
+    PlayingNow(Listen),
+    Listen(Listen),
+}
+
+/// Submits the playing song and listens to a `Server` from a background thread. Listens are kept
+/// in a queue in the data directory until the server accepts them, so they survive being offline
+/// and restarts.
+pub struct Scrobbler {
+    tx: Sender<Message>,
+}
+
+/// Listens submitted at once at most, as allowed by ListenBrainz.
+const BATCH: usize = 100;
+
+enum SubmitError {
+    /// The server won't ever accept these listens.
+    Rejected,
+    /// Offline, unauthorized, rate limited or the server is down: worth retrying later.
+    Failed,
+}
+
+struct Worker {
+    server: &'static Server,
+    agent: ureq::Agent,
+    queue_path: PathBuf,
+    queue: Vec<Listen>,
+}
+
+impl Worker {
+    fn submit(&self, listen_type: &str, payload: &[Listen]) -> Result<(), SubmitError> {
+        let url = format!("{}/1/submit-listens", self.server.url.trim_end_matches('/'));
+        let body = serde_json::json!({ "listen_type": listen_type, "payload": payload });
+        match self
+            .agent
+            .post(&url)
+            .set("Authorization", &format!("Token {}", self.server.token))
+            .send_json(body)
+        {
+            Ok(_) => Ok(()),
+            Err(ureq::Error::Status(400, _)) => Err(SubmitError::Rejected),
+            Err(_) => Err(SubmitError::Failed),
+        }
+    }
+    fn save(&self) {
+        let dir = self.queue_path.parent().unwrap_or(Path::new("."));
+        let _ = std::fs::create_dir_all(dir).and_then(|_| {
+            std::fs::write(
+                &self.queue_path,
+                serde_json::to_string(&self.queue).unwrap_or_default(),
+            )
+        });
+    }
+    /// Submits the queued listens, oldest first, until the server fails.
+    fn flush(&mut self) {
+        // Listens left to submit one by one, after a batch of them was rejected: one invalid
+        // listen rejects the whole batch, and only it should be dropped
+        let mut singles = 0;
+        while !self.queue.is_empty() {
+            let n = match singles {
+                0 => self.queue.len().min(BATCH),
+                _ => 1,
+            };
+            let listen_type = if n == 1 { "single" } else { "import" };
+            match self.submit(listen_type, &self.queue[..n]) {
+                Ok(()) => {}
+                Err(SubmitError::Rejected) if n == 1 => {}
+                Err(SubmitError::Rejected) => {
+                    singles = n;
+                    continue;
+                }
+                Err(SubmitError::Failed) => break,
+            }
+            self.queue.drain(..n);
+            self.save();
+            singles -= singles.min(1);
+        }
+    }
+    fn run(mut self, rx: Receiver<Message>) {
+        self.flush();
+        loop {
+            let message = if self.queue.is_empty() {
+                rx.recv().map_err(|_| RecvTimeoutError::Disconnected)
+            } else {
+                rx.recv_timeout(Duration::from_secs(SCROBBLE_RETRY_SECS))
+            };
+            match message {
+                Ok(Message::PlayingNow(x)) => {
+                    let _ = self.submit("playing_now", &[x]);
+                }
+                Ok(Message::Listen(x)) => {
+                    self.queue.push(x);
+                    self.save();
+                }
+                Err(RecvTimeoutError::Timeout) => {}
+                Err(RecvTimeoutError::Disconnected) => break,
+            }
+            self.flush();
+        }
+    }
+}
+
+/// Reads the queue of listens not submitted yet. Listens that can't be read are skipped, and a
+/// file that can't be read at all is kept aside rather than overwritten.
+fn load_queue(path: &Path) -> anyhow::Result<Vec<Listen>> {
+    match std::fs::read_to_string(path) {
+        Ok(x) => match serde_json::from_str::<Vec<serde_json::Value>>(&x) {
+            Ok(x) => Ok(x
+                .into_iter()
+                .filter_map(|x| serde_json::from_value(x).ok())
+                .collect()),
+            Err(_) => {
+                std::fs::copy(path, path.with_extension("json.bak"))?;
+                Ok(Vec::new())
+            }
+        },
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(Vec::new()),
+        Err(e) => Err(e.into()),
+    }
+}
+
+impl Scrobbler {
+    /// Loads the queue of listens not submitted yet and starts submitting them.
+    pub fn start(server: &'static Server) -> anyhow::Result<Self> {
+        let queue_path = data_dir().join("listenbrainz_queue.json");
+        let queue = load_queue(&queue_path)?;
+        let worker = Worker {
+            server,
+            agent: ureq::AgentBuilder::new()
+                .timeout(Duration::from_secs(10))
+                .build(),
+            queue_path,
+            queue,
+        };
+        let (tx, rx) = std::sync::mpsc::channel();
+        std::thread::spawn(move || worker.run(rx));
+        Ok(Self { tx })
+    }
+    pub fn playing_now(&self, song: &Song) {
+        let _ = self.tx.send(Message::PlayingNow(Listen::new(song, None)));
+    }
+    /// Queues a listen of `song`, which started playing at `listened_at`, in seconds since the
+    /// Unix epoch.
+    pub fn listen(&self, song: &Song, listened_at: u64) {
+        let _ = self
+            .tx
+            .send(Message::Listen(Listen::new(song, Some(listened_at))));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::{BufRead, BufReader, Read, Write};
+    use std::net::TcpListener;
+
+    fn listen(track_name: &str) -> Listen {
+        Listen {
+            listened_at: Some(1700000000),
+            track_metadata: TrackMetadata {
+                artist_name: "Artist".to_owned(),
+                track_name: track_name.to_owned(),
+                release_name: None,
+                additional_info: AdditionalInfo {
+                    duration_ms: 1000,
+                    tracknumber: Some(1),
+                    media_player: "mmmmmusic".to_owned(),
+                    submission_client: "mmmmmusic".to_owned(),
+                    submission_client_version: env!("CARGO_PKG_VERSION").to_owned(),
+                },
+            },
+        }
+    }
+
+    /// Answers one request per status, in order, on a local port. Returns a worker submitting to
+    /// it with its queue in a fresh directory, and the thread yielding the requests'
+    /// `Authorization` headers and bodies.
+    fn mock(
+        name: &str,
+        statuses: Vec<u16>,
+    ) -> (
+        Worker,
+        std::thread::JoinHandle<Vec<(String, serde_json::Value)>>,
+    ) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        let server = Box::leak(Box::new(Server {
+            url: Box::leak(url.into_boxed_str()),
+            token: "secret",
+        }));
+        let handle = std::thread::spawn(move || {
+            let mut requests = Vec::new();
+            for status in statuses {
+                let (stream, _) = listener.accept().unwrap();
+                let mut reader = BufReader::new(stream);
+                let (mut authorization, mut length) = (String::new(), 0);
+                loop {
+                    let mut line = String::new();
+                    reader.read_line(&mut line).unwrap();
+                    let line = line.trim_end();
+                    if line.is_empty() {
+                        break;
+                    }
+                    let (key, value) = line.split_once(": ").unwrap_or((line, ""));
+                    match key.to_ascii_lowercase().as_str() {
+                        "authorization" => authorization = value.to_owned(),
+                        "content-length" => length = value.parse().unwrap(),
+                        _ => {}
+                    }
+                }
+                let mut body = vec![0; length];
+                reader.read_exact(&mut body).unwrap();
+                requests.push((authorization, serde_json::from_slice(&body).unwrap()));
+                write!(
+                    reader.get_mut(),
+                    "HTTP/1.1 {status} X\r\nContent-Length: 0\r\nConnection: close\r\n\r\n"
+                )
+                .unwrap();
+            }
+            requests
+        });
+        let dir = std::env::temp_dir().join(format!(
+            "mmmmmusic-listenbrainz-{}-{name}",
+            std::process::id()
+        ));
+        let _ = std::fs::remove_dir_all(&dir);
+        let worker = Worker {
+            server,
+            agent: ureq::AgentBuilder::new()
+                .timeout(Duration::from_secs(10))
+                .build(),
+            queue_path: dir.join("listenbrainz_queue.json"),
+            queue: Vec::new(),
+        };
+        (worker, handle)
+    }
+
+    fn track_names(body: &serde_json::Value) -> Vec<&str> {
+        body["payload"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|x| x["track_metadata"]["track_name"].as_str().unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn submits_queued_listens() {
+        let (mut worker, handle) = mock("submits", vec![200, 200]);
+        worker.queue = vec![listen("a")];
+        worker.flush();
+        worker.queue = vec![listen("b"), listen("c")];
+        worker.flush();
+        let requests = handle.join().unwrap();
+        assert_eq!(requests[0].0, "Token secret");
+        assert_eq!(requests[0].1["listen_type"], "single");
+        assert_eq!(
+            requests[0].1["payload"][0],
+            serde_json::json!({
+                "listened_at": 1700000000,
+                "track_metadata": {
+                    "artist_name": "Artist",
+                    "track_name": "a",
+                    "additional_info": {
+                        "duration_ms": 1000,
+                        "tracknumber": 1,
+                        "media_player": "mmmmmusic",
+                        "submission_client": "mmmmmusic",
+                        "submission_client_version": env!("CARGO_PKG_VERSION"),
+                    },
+                },
+            })
+        );
+        assert_eq!(requests[1].1["listen_type"], "import");
+        assert_eq!(track_names(&requests[1].1), ["b", "c"]);
+        assert!(worker.queue.is_empty());
+        assert!(load_queue(&worker.queue_path).unwrap().is_empty());
+    }
+
+    #[test]
+    fn drops_only_rejected_listens() {
+        let (mut worker, handle) = mock("rejected", vec![400, 200, 400, 200]);
+        worker.queue = vec![listen("a"), listen("b"), listen("c")];
+        worker.flush();
+        let requests = handle.join().unwrap();
+        let requests: Vec<_> = requests.iter().map(|x| track_names(&x.1)).collect();
+        assert_eq!(
+            requests,
+            [vec!["a", "b", "c"], vec!["a"], vec!["b"], vec!["c"]]
+        );
+        assert!(worker.queue.is_empty());
+        assert!(load_queue(&worker.queue_path).unwrap().is_empty());
+    }
+
+    #[test]
+    fn keeps_listens_when_failing() {
+        let (mut worker, handle) = mock("failed", vec![400, 200, 503]);
+        worker.queue = vec![listen("a"), listen("b"), listen("c")];
+        worker.flush();
+        handle.join().unwrap();
+        let queue = load_queue(&worker.queue_path).unwrap();
+        let queue: Vec<_> = queue
+            .iter()
+            .map(|x| x.track_metadata.track_name.as_str())
+            .collect();
+        assert_eq!(queue, ["b", "c"]);
+    }
+
+    #[test]
+    fn skips_unreadable_queue() {
+        let dir = std::env::temp_dir().join(format!(
+            "mmmmmusic-listenbrainz-{}-unreadable",
+            std::process::id()
+        ));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("listenbrainz_queue.json");
+        let a = serde_json::to_value(listen("a")).unwrap();
+        std::fs::write(
+            &path,
+            serde_json::json!([a, { "listened_at": "x" }]).to_string(),
+        )
+        .unwrap();
+        assert_eq!(load_queue(&path).unwrap().len(), 1);
+        std::fs::write(&path, "[{").unwrap();
+        assert!(load_queue(&path).unwrap().is_empty());
+        assert!(path.with_extension("json.bak").exists());
+    }
+}
@@ -0,0 +1,62 @@
+use crate::playlists::Entry;
+use std::path::Path;
+use std::time::Duration;
+
+/// Reads an (extended) M3U playlist. Relative paths are resolved against the playlist's directory.
+pub fn read(path: &Path) -> anyhow::Result<Vec<Entry>> {
+    let content = String::from_utf8_lossy(&std::fs::read(path)?).into_owned();
+    let base = path.parent().unwrap_or(Path::new(""));
+    let mut entries = Vec::new();
+    let mut info = None;
+    for line in content.lines() {
+        let line = line.trim_start_matches('\u{feff}').trim();
+        if let Some(x) = line.strip_prefix("#EXTINF:") {
+            info = Some(x.to_owned());
+        } else if line.is_empty() || line.starts_with('#') {
+            continue;
+        } else {
+            let mut entry = Entry {
+                path: crate::playlists::resolve(base, line),
+                ..Default::default()
+            };
+            if let Some((duration, display)) = info.take().as_ref().and_then(|x| x.split_once(','))
+            {
+                entry.duration = duration
+                    .split_whitespace()
+                    .next()
+                    .and_then(|x| x.parse::<i64>().ok())
+                    .filter(|x| *x > 0)
+                    .map(|x| Duration::from_secs(x as u64));
+                match display.split_once(" - ") {
+                    Some((artist, title)) => {
+                        entry.artist = Some(artist.to_owned());
+                        entry.title = Some(title.to_owned());
+                    }
+                    None if !display.is_empty() => entry.title = Some(display.to_owned()),
+                    None => {}
+                }
+            }
+            entries.push(entry);
+        }
+    }
+    Ok(entries)
+}
+
+/// Writes an extended M3U playlist.
+pub fn write(path: &Path, entries: &[Entry]) -> anyhow::Result<()> {
+    let mut content = "#EXTM3U\n".to_owned();
+    for entry in entries {
+        content += &format!(
+            "#EXTINF:{},{}\n{}\n",
+            entry.duration.map_or(-1, |x| x.as_secs() as i64),
+            match (&entry.artist, &entry.title) {
+                (Some(artist), Some(title)) if !artist.is_empty() => format!("{artist} - {title}"),
+                (_, Some(title)) => title.clone(),
+                (_, None) => String::new(),
+            },
+            entry.path
+        );
+    }
+    std::fs::write(path, content)?;
+    Ok(())
+}
@@ -1,31 +1,75 @@
 mod ui;
 use ui::{UiComponent, UiEvent, UiEventResult};
+mod chapters;
 mod config;
-use config::{sources, FOCUSED_FRAME_DELAY, UNFOCUSED_FRAME_DELAY};
+mod cover;
+mod cue;
+mod listenbrainz;
+mod m3u;
+mod naming;
+mod playlists;
+mod pls;
+mod query;
+mod rating;
+mod scrobbler_log;
+use config::{
+    sources, FOCUSED_FRAME_DELAY, LISTENBRAINZ, SEARCH_HISTORY_LEN, SESSION_SAVE_INTERVAL_SECS,
+    SHOW_COVER, SHOW_LEVEL_METERS, UNFOCUSED_FRAME_DELAY, VISUALIZER_HEIGHT,
+};
+mod resume;
+mod session;
+mod settings;
+mod smart;
+use session::Session;
+use settings::Settings;
 mod song;
+mod stats;
+mod tags;
+mod tap;
+mod xspf;
 use ratatui::prelude::*;
 use std::cell::RefCell;
 use std::rc::Rc;
-use std::time::Duration;
+use std::time::{Duration, Instant};
 use ui::Tui;
 
 pub struct Core {
     frame_delay: f64,
     focus: ui::Focus,
     status_line: ui::StatusLine,
+    finder: ui::Finder,
+    /// Past searches, the latest last.
+    search_history: Vec<String>,
+    /// The search recalled from the history, if any.
+    search_history_index: Option<usize>,
     library: Rc<RefCell<ui::Library>>,
+    /// Shown in place of the library while `dashboard_shown`.
+    dashboard: ui::Dashboard,
+    dashboard_shown: bool,
+    /// Takes all keys while open.
+    tag_editor: Option<ui::TagEditor>,
+    /// Takes all keys while open, too.
+    batch: Option<ui::BatchPreview>,
     playlist: Rc<RefCell<ui::Playlist>>,
     player: ui::Player,
+    cover: ui::Cover,
+    visualizer: ui::Visualizer,
 }
 impl Core {
     pub fn switch_focus(&mut self, focus: ui::Focus) {
         self.focus = focus;
+        // The library is hidden behind the dashboard
+        if let ui::Focus::Library = focus {
+            self.dashboard_shown = false;
+        }
         self.status_line.handle_event(UiEvent::FocusLost);
         self.library.borrow_mut().handle_event(UiEvent::FocusLost);
+        self.dashboard.handle_event(UiEvent::FocusLost);
         self.playlist.borrow_mut().handle_event(UiEvent::FocusLost);
         self.player.handle_event(UiEvent::FocusLost);
         match focus {
             ui::Focus::Library => self.library.borrow_mut().handle_event(UiEvent::FocusGained),
+            ui::Focus::Dashboard => self.dashboard.handle_event(UiEvent::FocusGained),
             ui::Focus::Playlist => self
                 .playlist
                 .borrow_mut()
@@ -34,10 +78,39 @@ impl Core {
             ui::Focus::StatusLine => self.status_line.handle_event(UiEvent::FocusGained),
         };
     }
+    /// Saves the session, and the settings along with it.
+    pub fn save_session(&self) -> anyhow::Result<()> {
+        let mut settings = Settings::default();
+        self.library.borrow().save_settings(&mut settings);
+        settings.save()?;
+        let mut session = Session {
+            focus: match self.focus {
+                ui::Focus::StatusLine | ui::Focus::Dashboard => ui::Focus::Library,
+                x => x,
+            },
+            search_history: self.search_history.clone(),
+            ..Default::default()
+        };
+        self.library.borrow().save_session(&mut session);
+        self.playlist.borrow().save_session(&mut session);
+        session.save()
+    }
+    pub fn restore_session(&mut self, session: &Session) {
+        self.search_history = session.search_history.clone();
+        self.library.borrow_mut().restore_session(session);
+        if let Err(e) = self.playlist.borrow_mut().restore_session(session) {
+            self.status_line = ui::StatusLine::Message(format!("Failed to play: {e:#}"));
+        }
+        self.switch_focus(session.focus);
+    }
 }
 
 #[tokio::main]
 async fn main() -> anyhow::Result<()> {
+    let args = std::env::args().skip(1).collect::<Vec<_>>();
+    if !args.is_empty() {
+        return naming::cli(&args);
+    }
     let mut tui = Tui::run()?;
     tui.setup_panic();
 
@@ -48,23 +121,115 @@ async fn main() -> anyhow::Result<()> {
         frame_delay: 1f64 / 10f64,
         focus: ui::Focus::Library,
         status_line: ui::StatusLine::NothingButHappy,
+        finder: ui::Finder::new(),
+        search_history: Vec::new(),
+        search_history_index: None,
         library: Rc::new(RefCell::new(ui::Library::new())),
+        dashboard: ui::Dashboard::new(),
+        dashboard_shown: false,
+        tag_editor: None,
+        batch: None,
         playlist: Rc::new(RefCell::new(ui::Playlist::new(&stream)?)),
         player: ui::Player::new(),
+        cover: ui::Cover::new(),
+        visualizer: ui::Visualizer::new(),
     };
+    let resume = resume::ResumePositions::load().unwrap_or_else(|e| {
+        core.status_line =
+            ui::StatusLine::Message(format!("Failed to load resume positions: {e:#}"));
+        resume::ResumePositions::new()
+    });
+    let resume = Rc::new(RefCell::new(resume));
+    core.library
+        .borrow_mut()
+        .set_ref_to_resume(Rc::clone(&resume));
     core.playlist
         .borrow_mut()
         .set_ref_to_library(Rc::clone(&core.library));
+    core.playlist
+        .borrow_mut()
+        .set_ref_to_resume(Rc::clone(&resume));
+    let stats = stats::Stats::load().unwrap_or_else(|e| {
+        core.status_line = ui::StatusLine::Message(format!(
+            "Failed to load the stats, changes won't be saved: {e:#}"
+        ));
+        stats::Stats::unsaved()
+    });
+    let stats = Rc::new(RefCell::new(stats));
+    core.library
+        .borrow_mut()
+        .set_ref_to_stats(Rc::clone(&stats));
+    core.playlist
+        .borrow_mut()
+        .set_ref_to_stats(Rc::clone(&stats));
+    if let Some(server) = &LISTENBRAINZ {
+        match listenbrainz::Scrobbler::start(server) {
+            Ok(x) => core.playlist.borrow_mut().set_scrobbler(x),
+            Err(e) => {
+                core.status_line = ui::StatusLine::Message(format!(
+                    "Failed to load the ListenBrainz queue, not submitting listens: {e:#}"
+                ));
+            }
+        }
+    }
     core.player.set_ref_to_playlist(Rc::clone(&core.playlist));
+    core.cover.set_ref_to_playlist(Rc::clone(&core.playlist));
+    core.visualizer
+        .set_ref_to_playlist(Rc::clone(&core.playlist));
+    core.dashboard.set_ref_to_library(Rc::clone(&core.library));
+    core.dashboard.set_ref_to_stats(Rc::clone(&stats));
+    core.finder.set_ref_to_library(Rc::clone(&core.library));
     sources(&mut core.library.borrow_mut())?;
+    let (saved_playlists, result) = playlists::load_all(&core.library.borrow());
+    for source in saved_playlists {
+        core.library.borrow_mut().add_source(source);
+    }
+    if let Err(e) = result {
+        core.status_line = ui::StatusLine::Message(format!("Failed to load a playlist: {e:#}"));
+    }
     core.library.borrow_mut().handle_event(UiEvent::FocusGained);
+    match Settings::load() {
+        Ok(x) => core.library.borrow_mut().restore_settings(&x),
+        Err(e) => {
+            core.status_line =
+                ui::StatusLine::Message(format!("Failed to load the settings: {e:#}"));
+        }
+    }
+    match Session::load() {
+        Ok(Some(session)) => core.restore_session(&session),
+        Ok(None) => {}
+        Err(e) => {
+            core.status_line =
+                ui::StatusLine::Message(format!("Failed to load the session: {e:#}"));
+        }
+    }
+    let mut session_saved_at = Instant::now();
 
     let mut event_stream = crossterm::event::EventStream::new();
     loop {
+        if session_saved_at.elapsed() >= Duration::from_secs(SESSION_SAVE_INTERVAL_SECS) {
+            let result = core.save_session();
+            // Not to interrupt typing in the status line
+            if let (Err(e), ui::StatusLine::NothingButHappy | ui::StatusLine::Message(_)) =
+                (result, &core.status_line)
+            {
+                core.status_line =
+                    ui::StatusLine::Message(format!("Failed to save the session: {e:#}"));
+            }
+            session_saved_at = Instant::now();
+        }
         core.status_line.handle_event(UiEvent::Tick);
         core.library.borrow_mut().handle_event(UiEvent::Tick);
         core.playlist.borrow_mut().handle_event(UiEvent::Tick);
         core.player.handle_event(UiEvent::Tick);
+        match core.status_line {
+            ui::StatusLine::Find(ref s) => {
+                core.finder.update(s);
+                core.library.borrow_mut().preview_find(s);
+            }
+            _ => core.finder.clear(),
+        }
+        let cover = SHOW_COVER && core.cover.has_cover();
         tui.draw(|f| {
             let status_line_and_others = Layout::default()
                 .direction(Direction::Vertical)
@@ -75,14 +240,53 @@ async fn main() -> anyhow::Result<()> {
                 .direction(Direction::Horizontal)
                 .constraints([Constraint::Min(0), Constraint::Length(30)])
                 .split(status_line_and_others[0]);
-            core.library.borrow().render(f, library_and_others[0]);
+            let library_and_visualizer = Layout::default()
+                .direction(Direction::Vertical)
+                .constraints([
+                    Constraint::Min(0),
+                    Constraint::Length(if core.visualizer.is_shown() {
+                        VISUALIZER_HEIGHT
+                    } else {
+                        0
+                    }),
+                ])
+                .split(library_and_others[0]);
+            if core.dashboard_shown {
+                core.dashboard.render(f, library_and_visualizer[0]);
+            } else {
+                core.library.borrow().render(f, library_and_visualizer[0]);
+            }
+            core.finder.render(f, library_and_visualizer[0]);
+            if let Some(x) = &core.tag_editor {
+                x.render(f, library_and_visualizer[0]);
+            }
+            if let Some(x) = &core.batch {
+                x.render(f, library_and_visualizer[0]);
+            }
+            core.visualizer.render(f, library_and_visualizer[1]);
+            // Square, with cells twice as high as they are wide
+            let cover_height = if cover {
+                library_and_others[1].width / 2 + 1
+            } else {
+                0
+            };
             let playlist_and_others = Layout::default()
                 .direction(Direction::Vertical)
-                .constraints([Constraint::Min(0), Constraint::Length(4)])
+                .constraints([
+                    Constraint::Length(cover_height),
+                    Constraint::Min(0),
+                    Constraint::Length(if SHOW_LEVEL_METERS { 7 } else { 5 }),
+                ])
                 .split(library_and_others[1]);
-            core.playlist.borrow_mut().render(f, playlist_and_others[0]);
-            core.player.render(f, playlist_and_others[1]);
+            if cover {
+                core.cover.render(f, playlist_and_others[0]);
+            }
+            core.playlist.borrow_mut().render(f, playlist_and_others[1]);
+            core.player.render(f, playlist_and_others[2]);
         })?;
+        if core.cover.draw_graphics()? {
+            tui.clear()?;
+        }
         tokio::select! {
             Some(Ok(e)) = event_stream.next() => {
                 use crossterm::event::Event as E;
@@ -93,43 +297,218 @@ async fn main() -> anyhow::Result<()> {
                         use crossterm::event::KeyCode as C;
                         use ui::Focus as F;
                         match (core.focus,c) {
+                            (_, c) if core.tag_editor.as_mut().is_some_and(|x| x.handle_event(UiEvent::Key(c))==UiEventResult::Handled) => {},
+                            (_, C::Enter) if core.tag_editor.is_some() => {
+                                let editor = core.tag_editor.take().unwrap();
+                                let (written, errors) = editor.save();
+                                core.library.borrow_mut().forget_metadata(&editor.paths());
+                                core.status_line = ui::StatusLine::Message(match errors.first() {
+                                    None => format!("Saved the tags of {written} songs"),
+                                    Some(e) => format!("Saved the tags of {written} songs, {} failed: {e:#}", errors.len()),
+                                });
+                            }
+                            (_, C::Esc) if core.tag_editor.is_some() => core.tag_editor = None,
+                            _ if core.tag_editor.is_some() => {},
+                            (_, c) if core.batch.as_mut().is_some_and(|x| x.handle_event(UiEvent::Key(c))==UiEventResult::Handled) => {},
+                            (_, C::Enter) if core.batch.is_some() => {
+                                let batch = core.batch.take().unwrap();
+                                let (done, result) = batch.plan.apply();
+                                for (from, to) in &done {
+                                    if from == to {
+                                        core.library.borrow_mut().forget_metadata(std::slice::from_ref(from));
+                                    } else {
+                                        core.library.borrow_mut().rename(from, to);
+                                        resume.borrow_mut().rename(from, to);
+                                        let _ = stats.borrow_mut().rename(from, to);
+                                    }
+                                }
+                                core.status_line = ui::StatusLine::Message(match result {
+                                    Ok(()) => format!("Done with {} files", done.len()),
+                                    Err(e) => format!("Done with {} files, {e:#}", done.len()),
+                                });
+                            }
+                            (_, C::Esc) if core.batch.is_some() => core.batch = None,
+                            _ if core.batch.is_some() => {},
                             (F::StatusLine, c) if core.status_line.handle_event(UiEvent::Key(c))==UiEventResult::Handled => {},
                             (F::Library, c) if core.library.borrow_mut().handle_event(UiEvent::Key(c))==UiEventResult::Handled => {},
                             (F::Playlist, c) if core.playlist.borrow_mut().handle_event(UiEvent::Key(c))==UiEventResult::Handled => {},
                             (F::Player, c) if core.player.handle_event(UiEvent::Key(c))==UiEventResult::Handled => {},
+                            (F::Dashboard, c) if core.dashboard.handle_event(UiEvent::Key(c))==UiEventResult::Handled => {},
 
-                            (F::Library, C::Char(']')) => core.switch_focus(F::Playlist),
+                            (F::Library | F::Dashboard, C::Char(']')) => core.switch_focus(F::Playlist),
+                            (F::Playlist, C::Char('[')) if core.dashboard_shown => core.switch_focus(F::Dashboard),
                             (F::Playlist, C::Char('[')) => core.switch_focus(F::Library),
                             (F::Playlist, C::Char(']')) => core.switch_focus(F::Player),
                             (F::Player, C::Char('[')) => core.switch_focus(F::Playlist),
 
                             (F::Library, C::Enter) => {
-                                match core.library.borrow().find_by_id(&core.library.borrow().selected_id()).unwrap() {
-                                    song::SourceItem::Song(id, _) => core.playlist.borrow_mut().play_song(*id),
-                                    song::SourceItem::Title(..) => {},
+                                let chapter_start = core.library.borrow().selected_chapter_start();
+                                let songs = core.library.borrow().selected_songs();
+                                let result = match (chapter_start, songs.as_slice()) {
+                                    (Some(start), [id]) => core.playlist.borrow_mut().play_song_at(*id, start),
+                                    _ => songs.iter().map(|id| core.playlist.borrow_mut().play_song(*id)).fold(Ok(()), Result::and),
+                                };
+                                if let Err(e) = result {
+                                    core.status_line = ui::StatusLine::Message(format!("Failed to play: {e:#}"));
                                 }
                             },
 
+                            (F::Library, C::Char('D')) => {
+                                core.dashboard_shown = true;
+                                core.switch_focus(F::Dashboard);
+                            }
+                            (F::Dashboard, C::Char('D')) => core.switch_focus(F::Library),
+
+                            (F::Library, C::Char('E')) => {
+                                let songs = core.library.borrow().selected_files();
+                                if songs.is_empty() {
+                                    core.status_line = ui::StatusLine::Message("No tags to edit".to_owned());
+                                } else {
+                                    core.tag_editor = Some(ui::TagEditor::new(songs));
+                                }
+                            }
+                            (F::Library, C::Char('T')) => {
+                                core.switch_focus(F::StatusLine);
+                                core.status_line = ui::StatusLine::TagsFromPaths("".to_owned());
+                            }
+                            (F::Library, C::Char('R')) => {
+                                core.switch_focus(F::StatusLine);
+                                core.status_line = ui::StatusLine::RenameFiles("".to_owned());
+                            }
+
+                            (F::Library, C::Char('f')) => {
+                                core.library.borrow_mut().toggle_filter();
+                                core.status_line = ui::StatusLine::Message(core.library.borrow().find_summary());
+                            }
+
+                            (F::Library | F::Playlist | F::Player, C::Char(c @ ('0'..='5' | '*'))) => {
+                                let song = match core.focus {
+                                    F::Library => core.library.borrow().selected_song(),
+                                    F::Playlist => core.playlist.borrow().selected_song(),
+                                    _ => core.playlist.borrow().playing(),
+                                };
+                                if let Some(song) = song {
+                                    let rated = match c {
+                                        '*' => stats.borrow_mut().toggle_loved(&song),
+                                        c => stats.borrow_mut().rate(&song, c as u8 - b'0'),
+                                    };
+                                    if let Err(e) = rated {
+                                        core.status_line = ui::StatusLine::Message(e.to_string());
+                                    }
+                                }
+                            }
+
                             (F::Player, C::Char(' ')) => core.playlist.borrow_mut().pause_or_resume(),
                             (F::Player, C::Char('l')) => core.playlist.borrow_mut().next_song(),
+                            (F::Player, C::Char('a')) => core.playlist.borrow_mut().set_loop_a(),
+                            (F::Player, C::Char('b')) => core.playlist.borrow_mut().set_loop_b(),
+                            (F::Player, C::Char('x')) => core.playlist.borrow_mut().clear_loop(),
+                            (F::Player, C::Char('s')) => core.playlist.borrow_mut().cycle_sleep_timer(),
+                            (F::Player, C::Char('n')) => core.playlist.borrow_mut().next_chapter(),
+                            (F::Player, C::Char('p')) => core.playlist.borrow_mut().prev_chapter(),
+                            (F::Player, C::Char('+')) => core.playlist.borrow_mut().volume_up(),
+                            (F::Player, C::Char('-')) => core.playlist.borrow_mut().volume_down(),
 
+                            (F::StatusLine, C::Up) if matches!(core.status_line, ui::StatusLine::Find(_)) => {
+                                let index = match core.search_history_index {
+                                    _ if core.search_history.is_empty() => None,
+                                    None => Some(core.search_history.len() - 1),
+                                    Some(x) => Some(x.saturating_sub(1)),
+                                };
+                                if let Some(x) = index {
+                                    core.search_history_index = index;
+                                    core.status_line = ui::StatusLine::Find(core.search_history[x].clone());
+                                }
+                            }
+                            (F::StatusLine, C::Down) if matches!(core.status_line, ui::StatusLine::Find(_)) => {
+                                core.search_history_index = core.search_history_index.map(|x| x + 1).filter(|x| *x < core.search_history.len());
+                                core.status_line = ui::StatusLine::Find(match core.search_history_index {
+                                    Some(x) => core.search_history[x].clone(),
+                                    None => String::new(),
+                                });
+                            }
                             (F::StatusLine, C::Backspace) => {
+                                if let ui::StatusLine::Find(_) = core.status_line {
+                                    core.library.borrow_mut().cancel_find();
+                                }
                                 core.status_line = ui::StatusLine::NothingButHappy;
                                 core.switch_focus(F::Library);
                             }
+                            (F::StatusLine, C::Tab) => core.finder.next(),
+                            (F::StatusLine, C::BackTab) => core.finder.prev(),
                             (F::StatusLine, C::Enter) => {
-                                if let ui::StatusLine::Find(ref s) = core.status_line {
-                                    core.library.borrow_mut().set_find(if s.is_empty() {None} else {Some(s.clone())});
-                                    core.switch_focus(F::Library);
+                                match core.status_line {
+                                    ui::StatusLine::Find(ref s) => {
+                                        let played = core.finder.selected().map_or(Ok(()), |id| core.playlist.borrow_mut().play_song(id));
+                                        if !s.trim().is_empty() {
+                                            core.search_history.retain(|x| x != s);
+                                            core.search_history.push(s.clone());
+                                            let excess = core.search_history.len().saturating_sub(SEARCH_HISTORY_LEN);
+                                            core.search_history.drain(..excess);
+                                        }
+                                        let query = Some(query::Query::parse(s)).filter(|x| !x.is_empty());
+                                        let searching = query.is_some();
+                                        core.library.borrow_mut().set_find(query);
+                                        core.status_line = match played {
+                                            Err(e) => ui::StatusLine::Message(format!("Failed to play: {e:#}")),
+                                            Ok(()) if searching => ui::StatusLine::Message(core.library.borrow().find_summary()),
+                                            Ok(()) => ui::StatusLine::NothingButHappy,
+                                        };
+                                        core.switch_focus(F::Library);
+                                    }
+                                    ui::StatusLine::SavePlaylist(ref s) => {
+                                        let songs = core.playlist.borrow().songs();
+                                        let ids = core.playlist.borrow().song_ids();
+                                        let saved = playlists::save(s, &songs, &core.library.borrow());
+                                        core.status_line = match saved {
+                                            Ok(source) => {
+                                                core.library.borrow_mut().replace_source(source, &ids);
+                                                ui::StatusLine::Message(format!("Saved playlist {s}"))
+                                            }
+                                            Err(e) => ui::StatusLine::Message(e.to_string()),
+                                        };
+                                        core.switch_focus(F::Playlist);
+                                    }
+                                    ui::StatusLine::TagsFromPaths(ref s) | ui::StatusLine::RenameFiles(ref s) => {
+                                        let songs = core.library.borrow().selected_files();
+                                        let (title, plan) = match core.status_line {
+                                            ui::StatusLine::TagsFromPaths(_) => ("Tags from paths", naming::Plan::tags_from_paths(s, &songs)),
+                                            _ => ("Rename files", naming::Plan::paths_from_tags(s, &songs)),
+                                        };
+                                        core.batch = Some(ui::BatchPreview::new(format!("{title}: {s}"), plan));
+                                        core.status_line = ui::StatusLine::NothingButHappy;
+                                        core.switch_focus(F::Library);
+                                    }
+                                    ui::StatusLine::ExportPlaylist(ref s) => {
+                                        let songs = core.playlist.borrow().songs();
+                                        core.status_line = match playlists::export(s, &songs) {
+                                            Ok(()) => ui::StatusLine::Message(format!("Exported playlist to {s}")),
+                                            Err(e) => ui::StatusLine::Message(e.to_string()),
+                                        };
+                                        core.switch_focus(F::Playlist);
+                                    }
+                                    _ => core.status_line = ui::StatusLine::NothingButHappy,
                                 }
-                                core.status_line = ui::StatusLine::NothingButHappy;
+                            }
+                            (F::Playlist, C::Char('w')) => {
+                                core.switch_focus(F::StatusLine);
+                                core.status_line = ui::StatusLine::SavePlaylist("".to_owned());
+                            }
+                            (F::Playlist, C::Char('e')) => {
+                                core.switch_focus(F::StatusLine);
+                                core.status_line = ui::StatusLine::ExportPlaylist("".to_owned());
+                            }
+                            (F::Library | F::Playlist | F::Player | F::Dashboard, C::Char('V')) => {
+                                core.visualizer.cycle_mode()
                             }
 
                             (_, C::Char('?')) => {
                                 core.focus = F::StatusLine;
                                 core.library.borrow_mut().handle_event(UiEvent::FocusLost);
+                                core.dashboard.handle_event(UiEvent::FocusLost);
                                 core.playlist.borrow_mut().handle_event(UiEvent::FocusLost);
                                 core.status_line = ui::StatusLine::Find("".to_owned());
+                                core.search_history_index = None;
                             }
                             (_, C::Char('q')) => break,
                             _ => {},
@@ -141,5 +520,8 @@ async fn main() -> anyhow::Result<()> {
             _ = tokio::time::sleep(Duration::from_secs_f64(core.frame_delay)) => {}
         }
     }
+    core.save_session()?;
+    resume.borrow().save()?;
+    stats.borrow().save()?;
     Ok(())
 }
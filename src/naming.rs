@@ -0,0 +1,281 @@
+use crate::resume::ResumePositions;
+use crate::song::Song;
+use crate::stats::Stats;
+use crate::tags::TagField;
+use std::cell::RefCell;
+use std::path::{Path, PathBuf};
+
+/// A part of a naming pattern such as `%artist% - %track% - %title%`.
+enum Token {
+    Text(String),
+    Field(TagField),
+}
+
+fn parse(pattern: &str) -> anyhow::Result<Vec<Token>> {
+    let mut tokens = Vec::new();
+    let mut parts = pattern.split('%');
+    if let Some(x) = parts.next().filter(|x| !x.is_empty()) {
+        tokens.push(Token::Text(x.to_owned()));
+    }
+    // Every other part between `%`s is a field name
+    while let Some(name) = parts.next() {
+        let field = match name.to_lowercase().as_str() {
+            "title" => TagField::Title,
+            "artist" => TagField::Artist,
+            "album" => TagField::Album,
+            "albumartist" => TagField::AlbumArtist,
+            "year" => TagField::Year,
+            "track" => TagField::Track,
+            "genre" => TagField::Genre,
+            x => anyhow::bail!("unknown field %{x}% in {pattern}"),
+        };
+        tokens.push(Token::Field(field));
+        match parts.next() {
+            Some(x) if !x.is_empty() => tokens.push(Token::Text(x.to_owned())),
+            Some(_) => {}
+            None => anyhow::bail!("unclosed % in {pattern}"),
+        }
+    }
+    Ok(tokens)
+}
+
+/// Matches `text` against the tokens, each field taking as little text as possible. Fields
+/// can't be empty, as writing an empty value would remove the tag.
+fn capture(tokens: &[Token], text: &str) -> Option<Tags> {
+    match tokens {
+        [] => text.is_empty().then(Vec::new),
+        [Token::Text(x), rest @ ..] => capture(rest, text.strip_prefix(x.as_str())?),
+        [Token::Field(field)] => {
+            let value = text.trim();
+            (!value.is_empty()).then(|| vec![(*field, value.to_owned())])
+        }
+        [Token::Field(field), rest @ ..] => {
+            let Some(Token::Text(next)) = rest.first() else {
+                // Two fields in a row can't be told apart
+                return None;
+            };
+            text.match_indices(next.as_str()).find_map(|(i, _)| {
+                let value = text[..i].trim();
+                if value.is_empty() {
+                    return None;
+                }
+                let mut captures = capture(rest, &text[i..])?;
+                captures.insert(0, (*field, value.to_owned()));
+                Some(captures)
+            })
+        }
+    }
+}
+
+/// Derives tags from the path of a file, without its extension. The pattern is matched against
+/// as many trailing path components as it has, e.g. `%artist%/%album%/%track% %title%`.
+pub fn tags_from_path(pattern: &str, path: &str) -> anyhow::Result<Tags> {
+    let tokens = parse(pattern)?;
+    let path = Path::new(path).with_extension("");
+    let components = path.iter().map(|x| x.to_string_lossy()).collect::<Vec<_>>();
+    let depth = pattern.matches('/').count() + 1;
+    let text = components[components.len().saturating_sub(depth)..].join("/");
+    let captures =
+        capture(&tokens, &text).ok_or_else(|| anyhow::anyhow!("doesn't match {pattern}"))?;
+    if let Some((field, x)) = captures.iter().find(|(field, x)| {
+        matches!(field, TagField::Year | TagField::Track) && x.parse::<u16>().is_err()
+    }) {
+        anyhow::bail!("{}: {x} is not a number", field.name());
+    }
+    Ok(captures)
+}
+
+/// Names a file after its tags, keeping it in its directory (or below, if the pattern has `/`s)
+/// with its extension. Track numbers are padded to two digits.
+pub fn path_from_tags(pattern: &str, song: &Song) -> anyhow::Result<PathBuf> {
+    let mut name = String::new();
+    for token in parse(pattern)? {
+        match token {
+            Token::Text(x) => name.push_str(&x),
+            Token::Field(field) => {
+                let value = field.get(song);
+                if value.is_empty() {
+                    anyhow::bail!("no {}", field.name().to_lowercase());
+                }
+                let value = match field {
+                    TagField::Track => format!("{value:0>2}"),
+                    _ => value,
+                };
+                // Tags can't add directories
+                name.push_str(&value.replace(['/', '\\', '\0'], "_"));
+            }
+        }
+    }
+    // Nor escape the file's directory: dots and spaces around each component are trimmed, so
+    // none is `.` or `..`, or hidden
+    let mut relative = PathBuf::new();
+    for component in name.split('/') {
+        let component = component.trim_matches([' ', '.']);
+        if component.is_empty() {
+            anyhow::bail!("empty path component in {name}");
+        }
+        relative.push(component);
+    }
+    if !relative
+        .components()
+        .all(|x| matches!(x, std::path::Component::Normal(_)))
+    {
+        anyhow::bail!("{name} is not a relative path");
+    }
+    let path = Path::new(song.path());
+    let mut name = relative;
+    if let Some(x) = path.extension() {
+        name.as_mut_os_string().push(".");
+        name.as_mut_os_string().push(x);
+    }
+    Ok(path.parent().unwrap_or(Path::new("")).join(name))
+}
+
+/// Renames a file, creating the directories it is moved to. Doesn't overwrite other files.
+pub fn rename(from: &str, to: &Path) -> anyhow::Result<()> {
+    if to.exists() {
+        anyhow::bail!("{} already exists", to.display());
+    }
+    if let Some(x) = to.parent() {
+        std::fs::create_dir_all(x)?;
+    }
+    std::fs::rename(from, to)?;
+    Ok(())
+}
+
+/// Tags derived from a path, as `(field, value)`.
+type Tags = Vec<(TagField, String)>;
+
+/// What a batch tool is about to do to each file, or why it can't.
+pub enum Plan {
+    Tags(Vec<(String, anyhow::Result<Tags>)>),
+    Rename(Vec<(String, anyhow::Result<PathBuf>)>),
+}
+
+impl Plan {
+    pub fn tags_from_paths(pattern: &str, songs: &[Song]) -> Self {
+        Plan::Tags(
+            songs
+                .iter()
+                .map(|x| (x.path().to_owned(), tags_from_path(pattern, x.path())))
+                .collect(),
+        )
+    }
+    pub fn paths_from_tags(pattern: &str, songs: &[Song]) -> Self {
+        Plan::Rename(
+            songs
+                .iter()
+                .map(|x| (x.path().to_owned(), path_from_tags(pattern, x)))
+                .collect(),
+        )
+    }
+    /// Describes what will be done to each file, e.g. for a dry run.
+    pub fn describe(&self) -> Vec<(String, anyhow::Result<String>)> {
+        match self {
+            Plan::Tags(x) => x
+                .iter()
+                .map(|(path, tags)| {
+                    let tags = tags.as_ref().map_err(|e| anyhow::anyhow!("{e}")).map(|x| {
+                        x.iter()
+                            .map(|(field, value)| format!("{}: {value}", field.name()))
+                            .collect::<Vec<_>>()
+                            .join(", ")
+                    });
+                    (path.clone(), tags)
+                })
+                .collect(),
+            Plan::Rename(x) => x
+                .iter()
+                .map(|(path, to)| {
+                    let to = to
+                        .as_ref()
+                        .map_err(|e| anyhow::anyhow!("{e}"))
+                        .map(|x| format!("→ {}", x.display()));
+                    (path.clone(), to)
+                })
+                .collect(),
+        }
+    }
+    /// Writes the tags or renames the files, skipping the ones that can't be. Returns the files
+    /// done, as `(old path, new path)`, and the first error.
+    pub fn apply(&self) -> (Vec<(String, String)>, anyhow::Result<()>) {
+        let mut done = Vec::new();
+        let mut result = Ok(());
+        let mut fail = |e: anyhow::Error, path: &str| {
+            if result.is_ok() {
+                result = Err(e.context(path.to_owned()));
+            }
+        };
+        match self {
+            Plan::Tags(x) => {
+                for (path, tags) in x {
+                    let Ok(tags) = tags else { continue };
+                    match crate::tags::write(path, tags) {
+                        Ok(()) => done.push((path.clone(), path.clone())),
+                        Err(e) => fail(e, path),
+                    }
+                }
+            }
+            Plan::Rename(x) => {
+                for (path, to) in x {
+                    let Ok(to) = to else { continue };
+                    match rename(path, to) {
+                        Ok(()) => done.push((path.clone(), to.to_string_lossy().into_owned())),
+                        Err(e) => fail(e, path),
+                    }
+                }
+            }
+        }
+        (done, result)
+    }
+}
+
+/// Runs a batch tool from the command line:
+///
+/// `mmmmmusic tags-from-paths [--dry-run] <pattern> <file>...` or
+/// `mmmmmusic rename [--dry-run] <pattern> <file>...`
+pub fn cli(args: &[String]) -> anyhow::Result<()> {
+    let usage = "usage: mmmmmusic (tags-from-paths | rename) [--dry-run] <pattern> <file>...";
+    let (command, args) = args.split_first().ok_or_else(|| anyhow::anyhow!(usage))?;
+    let (dry_run, args) = match args.split_first() {
+        Some((x, rest)) if x == "--dry-run" => (true, rest),
+        _ => (false, args),
+    };
+    let (pattern, files) = args.split_first().ok_or_else(|| anyhow::anyhow!(usage))?;
+    let songs = files
+        .iter()
+        .map(|x| Song::File {
+            path: x.clone(),
+            metadata: RefCell::new(None),
+        })
+        .collect::<Vec<_>>();
+    let plan = match command.as_str() {
+        "tags-from-paths" => Plan::tags_from_paths(pattern, &songs),
+        "rename" => Plan::paths_from_tags(pattern, &songs),
+        _ => anyhow::bail!(usage),
+    };
+    for (path, x) in plan.describe() {
+        match x {
+            Ok(x) => println!("{path} {x}"),
+            Err(e) => println!("{path} skipped: {e}"),
+        }
+    }
+    if !dry_run {
+        let (done, result) = plan.apply();
+        println!("{} of {} files done", done.len(), songs.len());
+        // Play counts, ratings and resume positions follow renamed files, as in the library.
+        // They are keyed by path, so files must be given as the library finds them
+        let renamed = done.iter().filter(|(from, to)| from != to);
+        if renamed.clone().next().is_some() {
+            let mut stats = Stats::load()?;
+            let mut resume = ResumePositions::load()?;
+            for (from, to) in renamed {
+                stats.rename(from, to)?;
+                resume.rename(from, to);
+            }
+            resume.save()?;
+        }
+        result?;
+    }
+    Ok(())
+}
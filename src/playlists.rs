@@ -0,0 +1,135 @@
+use crate::config::data_dir;
+use crate::song::{Song, Source};
+use crate::ui::Library;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+/// A song as listed in a playlist file.
+#[derive(Default)]
+pub struct Entry {
+    pub path: String,
+    pub title: Option<String>,
+    pub artist: Option<String>,
+    pub duration: Option<Duration>,
+}
+
+impl Entry {
+    pub fn from_song(song: &Song) -> Self {
+        Self {
+            path: song.path().to_owned(),
+            title: song.get_title().ok().map(|x| x.to_owned()),
+            artist: song.get_artist().ok().map(|x| x.to_owned()),
+            duration: song.get_duration().ok().filter(|x| !x.is_zero()),
+        }
+    }
+    /// Whether the song looks like the one this entry refers to, going by title, artist and
+    /// duration. Used when the entry's path does not exist.
+    pub fn matches(&self, song: &Song) -> bool {
+        let same = |x: &str, y: &str| x.trim().to_lowercase() == y.trim().to_lowercase();
+        let Some(ref title) = self.title else {
+            return false;
+        };
+        same(title, song.get_title().unwrap_or(""))
+            && self
+                .artist
+                .as_ref()
+                .is_none_or(|x| same(x, song.get_artist().unwrap_or("")))
+            && match (self.duration, song.get_duration()) {
+                (Some(x), Ok(y)) if !y.is_zero() => x.abs_diff(y) <= Duration::from_secs(3),
+                _ => true,
+            }
+    }
+}
+
+/// Turns a path or `file://` URL found in a playlist file into an absolute path.
+pub fn resolve(base: &Path, location: &str) -> String {
+    let location = location.strip_prefix("file://").unwrap_or(location);
+    base.join(location).to_string_lossy().into_owned()
+}
+
+pub fn is_playlist(path: &Path) -> bool {
+    matches!(
+        path.extension()
+            .and_then(|x| x.to_str())
+            .map(|x| x.to_ascii_lowercase())
+            .as_deref(),
+        Some("m3u" | "m3u8" | "pls" | "xspf")
+    )
+}
+
+/// Writes a playlist file, picking the format by its extension.
+pub fn write(path: &Path, entries: &[Entry]) -> anyhow::Result<()> {
+    match path
+        .extension()
+        .and_then(|x| x.to_str())
+        .map(|x| x.to_ascii_lowercase())
+        .as_deref()
+    {
+        Some("m3u" | "m3u8") => crate::m3u::write(path, entries),
+        Some("pls") => crate::pls::write(path, entries),
+        Some("xspf") => crate::xspf::write(path, entries),
+        _ => anyhow::bail!("unknown playlist format: {}", path.display()),
+    }
+}
+
+/// Writes songs to a playlist file, picking the format by its extension.
+pub fn export(path: &str, songs: &[Song]) -> anyhow::Result<()> {
+    write(
+        Path::new(path),
+        &songs.iter().map(Entry::from_song).collect::<Vec<_>>(),
+    )
+}
+
+/// Where named playlists are saved, one `.m3u8` file each.
+pub fn dir() -> PathBuf {
+    data_dir().join("playlists")
+}
+
+/// Reads every saved named playlist as a library source, skipping the ones that can't be.
+/// Returns the playlists read and the first error.
+pub fn load_all(library: &Library) -> (Vec<Source>, anyhow::Result<()>) {
+    let entries = match std::fs::read_dir(dir()) {
+        Ok(x) => x,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return (Vec::new(), Ok(())),
+        Err(e) => return (Vec::new(), Err(e.into())),
+    };
+    let mut result = Ok(());
+    let mut fail = |e: anyhow::Error| {
+        if result.is_ok() {
+            result = Err(e);
+        }
+    };
+    let mut paths = entries
+        .filter_map(|x| x.map(|x| x.path()).map_err(|e| fail(e.into())).ok())
+        .collect::<Vec<_>>();
+    paths.sort();
+    let sources = paths
+        .into_iter()
+        .filter(|x| is_playlist(x))
+        .filter_map(|x| {
+            let name = x.file_stem().unwrap().to_string_lossy().into_owned();
+            Source::from_playlist(Some(name), x.to_string_lossy().into_owned(), library)
+                .map_err(|e| fail(e.context(x.display().to_string())))
+                .ok()
+        })
+        .collect();
+    (sources, result)
+}
+
+/// Saves songs as the named playlist `name`, and returns it as a library source.
+pub fn save(name: &str, songs: &[Song], library: &Library) -> anyhow::Result<Source> {
+    if name.is_empty() || name.contains(['/', '\\']) {
+        anyhow::bail!("invalid playlist name: {name:?}");
+    }
+    std::fs::create_dir_all(dir())?;
+    let path = dir().join(format!("{name}.m3u8"));
+    crate::m3u::write(
+        &path,
+        &songs.iter().map(Entry::from_song).collect::<Vec<_>>(),
+    )?;
+    Source::from_playlist(
+        Some(name.to_owned()),
+        path.to_string_lossy().into_owned(),
+        library,
+    )
+}
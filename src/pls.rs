@@ -0,0 +1,66 @@
+use crate::playlists::Entry;
+use std::collections::BTreeMap;
+use std::path::Path;
+use std::time::Duration;
+
+/// Reads a PLS playlist. Relative paths are resolved against the playlist's directory.
+pub fn read(path: &Path) -> anyhow::Result<Vec<Entry>> {
+    let content = String::from_utf8_lossy(&std::fs::read(path)?).into_owned();
+    let base = path.parent().unwrap_or(Path::new(""));
+    let mut entries = BTreeMap::<usize, Entry>::new();
+    for line in content.lines() {
+        let Some((key, value)) = line.trim().split_once('=') else {
+            continue;
+        };
+        let split = key.find(|x: char| x.is_ascii_digit()).unwrap_or(key.len());
+        let Ok(index) = key[split..].parse::<usize>() else {
+            continue;
+        };
+        let entry = entries.entry(index).or_default();
+        match key[..split].to_ascii_lowercase().as_str() {
+            "file" => entry.path = crate::playlists::resolve(base, value),
+            "title" => match value.split_once(" - ") {
+                Some((artist, title)) => {
+                    entry.artist = Some(artist.to_owned());
+                    entry.title = Some(title.to_owned());
+                }
+                None => entry.title = Some(value.to_owned()),
+            },
+            "length" => {
+                entry.duration = value
+                    .parse::<i64>()
+                    .ok()
+                    .filter(|x| *x > 0)
+                    .map(|x| Duration::from_secs(x as u64))
+            }
+            _ => {}
+        }
+    }
+    Ok(entries
+        .into_values()
+        .filter(|x| !x.path.is_empty())
+        .collect())
+}
+
+/// Writes a PLS playlist.
+pub fn write(path: &Path, entries: &[Entry]) -> anyhow::Result<()> {
+    let mut content = "[playlist]\n".to_owned();
+    for (i, entry) in entries.iter().enumerate() {
+        let i = i + 1;
+        content += &format!("File{i}={}\n", entry.path);
+        match (&entry.artist, &entry.title) {
+            (Some(artist), Some(title)) if !artist.is_empty() => {
+                content += &format!("Title{i}={artist} - {title}\n")
+            }
+            (_, Some(title)) => content += &format!("Title{i}={title}\n"),
+            (_, None) => {}
+        }
+        content += &format!(
+            "Length{i}={}\n",
+            entry.duration.map_or(-1, |x| x.as_secs() as i64)
+        );
+    }
+    content += &format!("NumberOfEntries={}\nVersion=2\n", entries.len());
+    std::fs::write(path, content)?;
+    Ok(())
+}
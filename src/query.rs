@@ -0,0 +1,262 @@
+use crate::song::Song;
+use crate::stats::{now, Stats};
+use std::borrow::Cow;
+use unicode_normalization::char::is_combining_mark;
+use unicode_normalization::UnicodeNormalization;
+
+/// A field of a song that can be searched with `field:value`.
+#[derive(Clone, Copy, PartialEq)]
+pub enum Field {
+    Title,
+    Artist,
+    Album,
+    AlbumArtist,
+    Genre,
+    Year,
+    Path,
+    /// Days since the song was added, going by the file's modification time.
+    Added,
+    Plays,
+    Skips,
+    /// Days since the song was last played.
+    LastPlayed,
+    /// 0 to 5 stars.
+    Rating,
+    /// 1 if loved, 0 if not.
+    Loved,
+}
+
+impl Field {
+    fn parse(name: &str) -> Option<Self> {
+        Some(match name.to_lowercase().as_str() {
+            "title" => Field::Title,
+            "artist" => Field::Artist,
+            "album" => Field::Album,
+            "albumartist" => Field::AlbumArtist,
+            "genre" => Field::Genre,
+            "year" => Field::Year,
+            "path" => Field::Path,
+            "added" => Field::Added,
+            "plays" => Field::Plays,
+            "skips" => Field::Skips,
+            "lastplayed" => Field::LastPlayed,
+            "rating" => Field::Rating,
+            "loved" => Field::Loved,
+            _ => return None,
+        })
+    }
+    /// Whether the field takes a number or a range of numbers.
+    fn is_numeric(self) -> bool {
+        matches!(
+            self,
+            Field::Year
+                | Field::Added
+                | Field::Plays
+                | Field::Skips
+                | Field::LastPlayed
+                | Field::Rating
+                | Field::Loved
+        )
+    }
+    /// The fields searched by terms without a field.
+    const TEXT: [Field; 4] = [
+        Field::Title,
+        Field::Artist,
+        Field::Album,
+        Field::AlbumArtist,
+    ];
+}
+
+enum Value {
+    /// Normalized text, matched anywhere in the field.
+    Text(String),
+    /// An inclusive range of numbers, either end may be open.
+    Range(Option<i64>, Option<i64>),
+}
+
+struct Term {
+    negated: bool,
+    field: Option<Field>,
+    value: Value,
+}
+
+/// A search query such as `artist:beatles year:1965..1969 -genre:live "exact phrase"`.
+///
+/// All terms have to match. A term is a word or a quoted phrase, optionally prefixed with
+/// `field:` and negated with a leading `-`. Terms without a field match the title, artist, album
+/// or album artist. Text is matched case-insensitively and ignoring accents. Numeric fields
+/// (`year:`, `plays:`, `skips:`, `rating:`, `loved:` as 0 or 1, and `added:` and `lastplayed:` in
+/// days ago) take a number or a range like `1965..1969`, `..1970` or `2000..`.
+pub struct Query {
+    terms: Vec<Term>,
+    /// Terms left out as their value is not a number or range, e.g. `year:sixties`.
+    invalid: Vec<String>,
+}
+
+/// Folds text for matching: compatibility decomposition without combining marks, lowercased.
+pub fn normalize(text: &str) -> String {
+    text.nfkd()
+        .filter(|x| !is_combining_mark(*x))
+        .flat_map(char::to_lowercase)
+        .collect()
+}
+
+/// Splits a query into words, keeping quoted strings together, e.g. `artist:"the who"`.
+fn words(query: &str) -> Vec<String> {
+    let mut words = Vec::new();
+    let mut chars = query.chars();
+    while let Some(c) = chars.next() {
+        if c.is_whitespace() {
+            continue;
+        }
+        let mut word = String::new();
+        let mut quoted = false;
+        let mut c = Some(c);
+        while let Some(x) = c {
+            match x {
+                '"' => quoted = !quoted,
+                x if x.is_whitespace() && !quoted => break,
+                x => word.push(x),
+            }
+            c = chars.next();
+        }
+        words.push(word);
+    }
+    words
+}
+
+fn parse_range(value: &str) -> Option<Value> {
+    let number = |x: &str| match x.trim() {
+        "" => Some(None),
+        x => x.parse().ok().map(Some),
+    };
+    match value.split_once("..") {
+        Some((a, b)) => Some(Value::Range(number(a)?, number(b)?)),
+        None => number(value)?.map(|x| Value::Range(Some(x), Some(x))),
+    }
+}
+
+impl Query {
+    pub fn parse(query: &str) -> Self {
+        let mut invalid = Vec::new();
+        let terms = words(query)
+            .into_iter()
+            .filter_map(|full| {
+                let word = full.as_str();
+                let (negated, word) = match word.strip_prefix('-') {
+                    Some(x) if !x.is_empty() => (true, x),
+                    _ => (false, word),
+                };
+                let (field, value) = match word.split_once(':') {
+                    Some((x, y)) => match Field::parse(x) {
+                        Some(x) => (Some(x), y),
+                        None => (None, word),
+                    },
+                    None => (None, word),
+                };
+                let value = match field {
+                    Some(x) if x.is_numeric() => match parse_range(value) {
+                        Some(x) => x,
+                        None => {
+                            invalid.push(full.clone());
+                            return None;
+                        }
+                    },
+                    _ => Value::Text(normalize(value)),
+                };
+                Some(Term {
+                    negated,
+                    field,
+                    value,
+                })
+            })
+            .collect();
+        Self { terms, invalid }
+    }
+    /// Whether there are no terms, not even invalid ones.
+    pub fn is_empty(&self) -> bool {
+        self.terms.is_empty() && self.invalid.is_empty()
+    }
+    /// The terms left out as their value is not a number or range.
+    pub fn invalid(&self) -> &[String] {
+        &self.invalid
+    }
+    /// Whether every term matches, getting the fields with `get`. Terms on fields `get` has no
+    /// value for don't match, so their negations do.
+    pub fn matches<'a>(&self, get: impl Fn(Field) -> Option<Cow<'a, str>>) -> bool {
+        self.matches_fields(get, false)
+    }
+    /// Like `matches`, for rows that only have some of the fields, e.g. an album: terms on the
+    /// fields `get` has no value for don't match, negated or not, so that `-genre:live` doesn't
+    /// match every album.
+    pub fn matches_some<'a>(&self, get: impl Fn(Field) -> Option<Cow<'a, str>>) -> bool {
+        self.matches_fields(get, true)
+    }
+    fn matches_fields<'a>(
+        &self,
+        get: impl Fn(Field) -> Option<Cow<'a, str>>,
+        partial: bool,
+    ) -> bool {
+        self.terms.iter().all(|term| {
+            let has = |field: Field| get(field).is_some();
+            let applies = match term.field {
+                Some(x) => has(x),
+                None => Field::TEXT.into_iter().any(has),
+            };
+            if partial && term.negated && !applies {
+                return false;
+            }
+            let matches = |field: Field| {
+                let Some(text) = get(field) else {
+                    return false;
+                };
+                match &term.value {
+                    Value::Text(x) => normalize(&text).contains(x.as_str()),
+                    Value::Range(a, b) => text
+                        .trim()
+                        .parse::<i64>()
+                        .is_ok_and(|x| a.is_none_or(|a| a <= x) && b.is_none_or(|b| x <= b)),
+                }
+            };
+            let matched = match term.field {
+                Some(x) => matches(x),
+                None => Field::TEXT.into_iter().any(matches),
+            };
+            matched != term.negated
+        })
+    }
+    pub fn matches_song(&self, song: &Song, stats: &Stats) -> bool {
+        self.matches(|field| field_of(song, field, stats))
+    }
+}
+
+fn field_of<'a>(song: &'a Song, field: Field, stats: &Stats) -> Option<Cow<'a, str>> {
+    const DAY_SECS: u64 = 24 * 60 * 60;
+    let song_stats = || stats.get(&song.key()).cloned().unwrap_or_default();
+    match field {
+        Field::Title => song.get_title().ok().map(Cow::Borrowed),
+        Field::Artist => song.get_artist().ok().map(Cow::Borrowed),
+        Field::Album => song.get_album().ok().map(Cow::Borrowed),
+        Field::AlbumArtist => song.get_album_artist().ok().map(Cow::Borrowed),
+        Field::Genre => song.get_genre().ok().map(Cow::Borrowed),
+        Field::Year => song
+            .get_year()
+            .ok()
+            .flatten()
+            .map(|x| Cow::Owned(x.to_string())),
+        Field::Path => Some(Cow::Borrowed(song.path())),
+        Field::Added => song
+            .get_modified()
+            .ok()
+            .flatten()
+            .and_then(|x| x.elapsed().ok())
+            .map(|x| Cow::Owned((x.as_secs() / DAY_SECS).to_string())),
+        Field::Plays => Some(Cow::Owned(song_stats().plays.to_string())),
+        Field::Skips => Some(Cow::Owned(song_stats().skips.to_string())),
+        Field::LastPlayed => song_stats()
+            .last_played
+            .map(|x| Cow::Owned((now().saturating_sub(x) / DAY_SECS).to_string())),
+        Field::Rating => Some(Cow::Owned(song_stats().rating.to_string())),
+        Field::Loved => Some(Cow::Borrowed(if song_stats().loved { "1" } else { "0" })),
+    }
+}
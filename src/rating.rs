@@ -0,0 +1,53 @@
+use id3::frame::{Content, Frame, Popularimeter};
+use id3::TagLike;
+
+/// The `POPM` user ratings are written for.
+const POPM_USER: &str = "mmmmmusic";
+
+/// `POPM` ratings for 0 to 5 stars, as most players map them.
+const POPM_RATINGS: [u8; 6] = [0, 1, 64, 128, 196, 255];
+
+/// Writes a rating of 0 to 5 stars to a file's tags, where 0 removes it: an ID3 `POPM` frame for
+/// MP3s, or an `FMPS_RATING` Vorbis comment for FLACs. Other formats are left alone.
+pub fn write_tag(path: &str, rating: u8) -> anyhow::Result<()> {
+    let rating = rating.min(5);
+    let extension = path.rsplit('.').next().unwrap_or("").to_ascii_lowercase();
+    match extension.as_str() {
+        "mp3" => {
+            let mut tag = match id3::Tag::read_from_path(path) {
+                Ok(x) => x,
+                Err(e) if matches!(e.kind, id3::ErrorKind::NoTag) => id3::Tag::new(),
+                Err(e) => return Err(e.into()),
+            };
+            // Ratings by other players are kept
+            let others = tag.remove("POPM").into_iter().filter(
+                |x| !matches!(x.content(), Content::Popularimeter(x) if x.user == POPM_USER),
+            );
+            for frame in others.collect::<Vec<_>>() {
+                tag.add_frame(frame);
+            }
+            if rating > 0 {
+                tag.add_frame(Frame::with_content(
+                    "POPM",
+                    Content::Popularimeter(Popularimeter {
+                        user: POPM_USER.to_owned(),
+                        rating: POPM_RATINGS[rating as usize],
+                        counter: 0,
+                    }),
+                ));
+            }
+            tag.write_to_path(path, tag.version())?;
+        }
+        "flac" => {
+            let mut tag = metaflac::Tag::read_from_path(path)?;
+            if rating > 0 {
+                tag.set_vorbis("FMPS_RATING", vec![(rating as f32 / 5.).to_string()]);
+            } else {
+                tag.remove_vorbis("FMPS_RATING");
+            }
+            tag.save()?;
+        }
+        _ => {}
+    }
+    Ok(())
+}
@@ -0,0 +1,47 @@
+use crate::config::data_dir;
+use std::collections::HashMap;
+use std::path::PathBuf;
+
+/// Positions to resume long songs from, in hundreds of milliseconds, keyed by `Song::key`.
+pub struct ResumePositions {
+    path: PathBuf,
+    positions: HashMap<String, u64>,
+}
+
+impl ResumePositions {
+    pub fn new() -> Self {
+        Self {
+            path: data_dir().join("resume.json"),
+            positions: HashMap::new(),
+        }
+    }
+    pub fn load() -> anyhow::Result<Self> {
+        let mut resume = Self::new();
+        match std::fs::read_to_string(&resume.path) {
+            Ok(x) => resume.positions = serde_json::from_str(&x)?,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+            Err(e) => return Err(e.into()),
+        }
+        Ok(resume)
+    }
+    pub fn save(&self) -> anyhow::Result<()> {
+        std::fs::create_dir_all(self.path.parent().unwrap())?;
+        std::fs::write(&self.path, serde_json::to_string(&self.positions)?)?;
+        Ok(())
+    }
+    pub fn get(&self, key: &str) -> Option<u64> {
+        self.positions.get(key).copied()
+    }
+    pub fn set(&mut self, key: String, position: u64) {
+        self.positions.insert(key, position);
+    }
+    /// Moves a position to the song's new key, e.g. after its file was renamed.
+    pub fn rename(&mut self, from: &str, to: &str) {
+        if let Some(x) = self.positions.remove(from) {
+            self.positions.insert(to.to_owned(), x);
+        }
+    }
+    pub fn remove(&mut self, key: &str) {
+        self.positions.remove(key);
+    }
+}
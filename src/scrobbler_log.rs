@@ -0,0 +1,41 @@
+use crate::song::Song;
+use std::io::Write;
+use std::path::Path;
+
+/// Appends a track to a `.scrobbler.log` in the Audioscrobbler portable player format (as written
+/// by Rockbox), creating it with its header if needed. `listened` tracks are rated `L`, skipped
+/// ones `S`, and `started` is when the track started playing, in seconds since the Unix epoch.
+pub fn append(path: &Path, song: &Song, listened: bool, started: u64) -> anyhow::Result<()> {
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir)?;
+    }
+    let mut file = std::fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)?;
+    if file.metadata()?.len() == 0 {
+        write!(
+            file,
+            "#AUDIOSCROBBLER/1.1\n#TZ/UTC\n#CLIENT/mmmmmusic {}\n",
+            env!("CARGO_PKG_VERSION")
+        )?;
+    }
+    // Fields are separated by tabs and records by newlines, so neither can appear in them
+    let field = |x: anyhow::Result<&str>| x.unwrap_or("").replace(['\t', '\n', '\r'], " ");
+    writeln!(
+        file,
+        "{}\t{}\t{}\t{}\t{}\t{}\t{}\t",
+        field(song.get_artist()),
+        field(song.get_album()),
+        field(song.get_title()),
+        song.get_track()
+            .ok()
+            .flatten()
+            .map(|x| x.to_string())
+            .unwrap_or_default(),
+        song.get_duration().unwrap_or_default().as_secs(),
+        if listened { "L" } else { "S" },
+        started
+    )?;
+    Ok(())
+}
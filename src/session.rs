@@ -0,0 +1,49 @@
+use crate::config::data_dir;
+use crate::ui::Focus;
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+
+/// Everything needed to pick up where the last run left off.
+#[derive(Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Session {
+    pub focus: Focus,
+    pub library_tree: bool,
+    pub library_selected: usize,
+    /// `Song::key`s of the playlist items.
+    pub queue: Vec<String>,
+    pub playing: Option<usize>,
+    pub progress_hundred_ms: u64,
+    pub paused: bool,
+    pub volume_percent: u64,
+    pub loop_points: (Option<u64>, Option<u64>),
+    /// Past searches, the latest last.
+    pub search_history: Vec<String>,
+}
+
+impl Session {
+    fn path() -> PathBuf {
+        data_dir().join("session.json")
+    }
+    /// Loads the last session. One that can't be read is kept aside as `session.json.bak`, as
+    /// the next save overwrites it.
+    pub fn load() -> anyhow::Result<Option<Self>> {
+        let path = Self::path();
+        match std::fs::read_to_string(&path) {
+            Ok(x) => match serde_json::from_str(&x) {
+                Ok(x) => Ok(Some(x)),
+                Err(e) => {
+                    std::fs::copy(&path, path.with_extension("json.bak"))?;
+                    Err(anyhow::Error::from(e).context("kept as session.json.bak"))
+                }
+            },
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(e.into()),
+        }
+    }
+    pub fn save(&self) -> anyhow::Result<()> {
+        std::fs::create_dir_all(data_dir())?;
+        std::fs::write(Self::path(), serde_json::to_string(self)?)?;
+        Ok(())
+    }
+}
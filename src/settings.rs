@@ -0,0 +1,40 @@
+use crate::config::config_dir;
+use crate::ui::Column;
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+
+/// Configuration changed from the ui, kept in the config directory unlike the session.
+#[derive(Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Settings {
+    /// Empty if the columns were never changed, to use `LIBRARY_COLUMNS`.
+    pub library_columns: Vec<(Column, u16)>,
+    pub library_sort: Option<(Column, bool)>,
+}
+
+impl Settings {
+    fn path() -> PathBuf {
+        config_dir().join("settings.json")
+    }
+    /// Loads the settings. Ones that can't be read are kept aside as `settings.json.bak`, as the
+    /// next save overwrites them.
+    pub fn load() -> anyhow::Result<Self> {
+        let path = Self::path();
+        match std::fs::read_to_string(&path) {
+            Ok(x) => match serde_json::from_str(&x) {
+                Ok(x) => Ok(x),
+                Err(e) => {
+                    std::fs::copy(&path, path.with_extension("json.bak"))?;
+                    Err(anyhow::Error::from(e).context("kept as settings.json.bak"))
+                }
+            },
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(Self::default()),
+            Err(e) => Err(e.into()),
+        }
+    }
+    pub fn save(&self) -> anyhow::Result<()> {
+        std::fs::create_dir_all(config_dir())?;
+        std::fs::write(Self::path(), serde_json::to_string(self)?)?;
+        Ok(())
+    }
+}
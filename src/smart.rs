@@ -0,0 +1,54 @@
+use crate::query::Query;
+use crate::song::Song;
+use crate::ui::Library;
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
+use uuid::Uuid;
+
+/// A playlist of the songs matching a query, shown as a source in the library and updated
+/// whenever the library changes.
+pub struct SmartPlaylist {
+    pub title: &'static str,
+    /// A search query, see `Query`.
+    pub query: &'static str,
+    pub order: Order,
+    /// How many songs at most, after ordering them.
+    pub limit: Option<usize>,
+}
+
+pub enum Order {
+    /// The order of the library.
+    Library,
+    Random,
+    /// The most recently added first.
+    Newest,
+}
+
+impl SmartPlaylist {
+    /// Picks the songs of the library for the playlist. The random order is drawn from `seed`,
+    /// so that evaluating the playlist again with it, e.g. after a play was counted, doesn't
+    /// shuffle it again.
+    pub fn evaluate(&self, library: &Library, seed: u64) -> Vec<Uuid> {
+        let query = Query::parse(self.query);
+        let stats = library.stats();
+        let mut songs = library
+            .songs()
+            .filter(|(_, s)| query.matches_song(s, &stats))
+            .collect::<Vec<(&Uuid, &Song)>>();
+        match self.order {
+            Order::Library => {}
+            Order::Random => songs.sort_by_cached_key(|(id, _)| {
+                let mut hasher = DefaultHasher::new();
+                (seed, id).hash(&mut hasher);
+                hasher.finish()
+            }),
+            Order::Newest => songs
+                .sort_by_cached_key(|(_, s)| std::cmp::Reverse(s.get_modified().ok().flatten())),
+        }
+        songs
+            .into_iter()
+            .take(self.limit.unwrap_or(usize::MAX))
+            .map(|(x, _)| *x)
+            .collect()
+    }
+}
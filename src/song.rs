@@ -1,5 +1,6 @@
+use crate::chapters::Chapter;
 use std::cell::RefCell;
-use std::time::Duration;
+use std::time::{Duration, SystemTime};
 use uuid::Uuid;
 
 #[derive(Clone)]
@@ -8,13 +9,51 @@ pub enum Song {
         path: String,
         metadata: RefCell<Option<SongMetadata>>,
     },
+    /// A part of a file, e.g. a track of a single-file album described by a CUE sheet.
+    Range {
+        path: String,
+        start: Duration,
+        end: Option<Duration>,
+        tags: RangeTags,
+        metadata: RefCell<Option<SongMetadata>>,
+    },
+}
+
+/// Tags of a `Song::Range`, taking precedence over the ones in the file.
+#[derive(Clone, Default)]
+pub struct RangeTags {
+    pub title: Option<String>,
+    pub artist: Option<String>,
+    pub album: Option<String>,
+    pub album_artist: Option<String>,
+    pub year: Option<i32>,
+    pub track: Option<u16>,
+    pub genre: Option<String>,
 }
 
 #[derive(Clone)]
 pub struct SongMetadata {
     title: String,
     artist: String,
+    album: String,
+    album_artist: String,
+    year: Option<i32>,
+    track: Option<u16>,
+    disc: Option<u16>,
+    genre: String,
+    /// Average bitrate of the file in kbps.
+    bitrate: Option<u32>,
+    /// When the file was last modified, taken as when it was added to the library.
+    modified: Option<SystemTime>,
     duration: Duration,
+    chapters: Vec<Chapter>,
+}
+
+/// Estimates the average bitrate of a file in kbps from its size and duration in seconds.
+fn bitrate(path: &str, duration: Option<f64>) -> Option<u32> {
+    let size = std::fs::metadata(path).ok()?.len();
+    let duration = duration.filter(|x| *x > 0.)?;
+    Some((size as f64 * 8. / 1000. / duration).round() as u32)
 }
 
 impl Song {
@@ -26,68 +65,247 @@ impl Song {
                     title: tag
                         .title()
                         .map(|x| x.to_string())
-                        .unwrap_or_else(|| path.split('/').last().unwrap().to_owned()),
+                        .unwrap_or_else(|| path.split('/').next_back().unwrap().to_owned()),
                     artist: tag.artist().map(|x| x.to_string()).unwrap_or_default(),
+                    album: tag.album_title().map(|x| x.to_string()).unwrap_or_default(),
+                    album_artist: tag
+                        .album_artist()
+                        .map(|x| x.to_string())
+                        .unwrap_or_default(),
+                    year: tag.year(),
+                    track: tag.track_number(),
+                    disc: tag.disc_number(),
+                    genre: tag.genre().map(|x| x.to_string()).unwrap_or_default(),
+                    bitrate: bitrate(path, tag.duration()),
+                    modified: std::fs::metadata(path).and_then(|x| x.modified()).ok(),
                     duration: tag
                         .duration()
                         .map(|x| Duration::from_secs(x as u64))
                         .unwrap_or_default(),
+                    chapters: crate::chapters::read(path).unwrap_or_default(),
+                })
+            }
+            Self::Range {
+                path,
+                start,
+                end,
+                tags,
+                ..
+            } => {
+                // The file may well be in a format audiotags can't read, e.g. WAV
+                let tag = audiotags::Tag::new().read_from_path(path).ok();
+                let tag = tag.as_ref();
+                Ok(SongMetadata {
+                    title: tags
+                        .title
+                        .clone()
+                        .or_else(|| tag.and_then(|x| x.title()).map(|x| x.to_string()))
+                        .unwrap_or_else(|| path.split('/').next_back().unwrap().to_owned()),
+                    artist: tags
+                        .artist
+                        .clone()
+                        .or_else(|| tag.and_then(|x| x.artist()).map(|x| x.to_string()))
+                        .unwrap_or_default(),
+                    album: tags
+                        .album
+                        .clone()
+                        .or_else(|| tag.and_then(|x| x.album_title()).map(|x| x.to_string()))
+                        .unwrap_or_default(),
+                    album_artist: tags
+                        .album_artist
+                        .clone()
+                        .or_else(|| tag.and_then(|x| x.album_artist()).map(|x| x.to_string()))
+                        .unwrap_or_default(),
+                    year: tags.year.or_else(|| tag.and_then(|x| x.year())),
+                    track: tags.track,
+                    disc: tag.and_then(|x| x.disc_number()),
+                    genre: tags
+                        .genre
+                        .clone()
+                        .or_else(|| tag.and_then(|x| x.genre()).map(|x| x.to_string()))
+                        .unwrap_or_default(),
+                    bitrate: bitrate(path, tag.and_then(|x| x.duration())),
+                    modified: std::fs::metadata(path).and_then(|x| x.modified()).ok(),
+                    duration: match end {
+                        Some(end) => end.saturating_sub(*start),
+                        None => tag
+                            .and_then(|x| x.duration())
+                            .map(|x| Duration::from_secs(x as u64).saturating_sub(*start))
+                            .unwrap_or_default(),
+                    },
+                    chapters: Vec::new(),
                 })
             }
         }
     }
-    pub fn get_title(&self) -> anyhow::Result<&str> {
+    /// Identifies the song across runs, unlike the `Uuid`s handed out by `Source`.
+    pub fn key(&self) -> String {
         match self {
-            Self::File {
-                path: _path,
-                metadata,
-            } => {
-                if metadata.borrow().is_none() {
-                    *metadata.borrow_mut() = Some(self.read_metadata()?);
-                }
-                Ok(unsafe { &metadata.try_borrow_unguarded()?.as_ref().unwrap().title })
-            }
+            Self::File { path, .. } => path.clone(),
+            Self::Range { path, start, .. } => format!("{}#{}", path, start.as_millis()),
         }
     }
-    pub fn get_artist(&self) -> anyhow::Result<&str> {
+    pub fn path(&self) -> &str {
         match self {
-            Self::File {
-                path: _path,
-                metadata,
-            } => {
-                if metadata.borrow().is_none() {
-                    *metadata.borrow_mut() = Some(self.read_metadata()?);
-                }
-                Ok(unsafe { &metadata.try_borrow_unguarded()?.as_ref().unwrap().artist })
+            Self::File { path, .. } | Self::Range { path, .. } => path,
+        }
+    }
+    /// Drops the cached metadata, so that it is read again, e.g. after the tags were changed.
+    pub fn forget_metadata(&mut self) {
+        match self {
+            Self::File { metadata, .. } | Self::Range { metadata, .. } => {
+                *metadata.get_mut() = None
             }
         }
     }
-    pub fn get_duration(&self) -> anyhow::Result<Duration> {
+    /// Points the song to the file's new path after it was renamed.
+    pub fn rename(&mut self, to: String) {
         match self {
-            Self::File {
-                path: _path,
-                metadata,
-            } => {
+            Self::File { path, .. } | Self::Range { path, .. } => *path = to,
+        }
+        self.forget_metadata();
+    }
+    fn metadata(&self) -> anyhow::Result<&SongMetadata> {
+        match self {
+            Self::File { metadata, .. } | Self::Range { metadata, .. } => {
                 if metadata.borrow().is_none() {
                     *metadata.borrow_mut() = Some(self.read_metadata()?);
                 }
-                Ok(metadata.borrow().as_ref().unwrap().duration)
+                Ok(unsafe { metadata.try_borrow_unguarded()?.as_ref().unwrap() })
             }
         }
     }
+    pub fn get_title(&self) -> anyhow::Result<&str> {
+        Ok(&self.metadata()?.title)
+    }
+    pub fn get_artist(&self) -> anyhow::Result<&str> {
+        Ok(&self.metadata()?.artist)
+    }
+    pub fn get_album(&self) -> anyhow::Result<&str> {
+        Ok(&self.metadata()?.album)
+    }
+    pub fn get_album_artist(&self) -> anyhow::Result<&str> {
+        Ok(&self.metadata()?.album_artist)
+    }
+    pub fn get_year(&self) -> anyhow::Result<Option<i32>> {
+        Ok(self.metadata()?.year)
+    }
+    pub fn get_track(&self) -> anyhow::Result<Option<u16>> {
+        Ok(self.metadata()?.track)
+    }
+    pub fn get_disc(&self) -> anyhow::Result<Option<u16>> {
+        Ok(self.metadata()?.disc)
+    }
+    pub fn get_genre(&self) -> anyhow::Result<&str> {
+        Ok(&self.metadata()?.genre)
+    }
+    pub fn get_bitrate(&self) -> anyhow::Result<Option<u32>> {
+        Ok(self.metadata()?.bitrate)
+    }
+    pub fn get_modified(&self) -> anyhow::Result<Option<SystemTime>> {
+        Ok(self.metadata()?.modified)
+    }
+    pub fn get_duration(&self) -> anyhow::Result<Duration> {
+        Ok(self.metadata()?.duration)
+    }
+    pub fn get_chapters(&self) -> anyhow::Result<&[Chapter]> {
+        Ok(&self.metadata()?.chapters)
+    }
     pub fn decode(&self) -> anyhow::Result<impl rodio::Source<Item = f32> + Send + 'static> {
         use rodio::source::Source;
         match self {
-            Self::File { path, .. } => {
-                Ok(rodio::Decoder::new(std::fs::File::open(path)?)?.convert_samples())
-            }
+            Self::File { path, .. } => Range::new(
+                rodio::Decoder::new(std::fs::File::open(path)?)?.convert_samples(),
+                Duration::ZERO,
+                None,
+            ),
+            Self::Range {
+                path, start, end, ..
+            } => Range::new(
+                rodio::Decoder::new(std::fs::File::open(path)?)?.convert_samples(),
+                *start,
+                *end,
+            ),
         }
     }
 }
 
+/// Plays `start..end` of a source. Positions, e.g. for seeking, are relative to `start`.
+struct Range<S> {
+    inner: S,
+    start: Duration,
+    end: Option<Duration>,
+    played_samples: u64,
+}
+
+impl<S: rodio::Source<Item = f32>> Range<S> {
+    fn new(mut inner: S, start: Duration, end: Option<Duration>) -> anyhow::Result<Self> {
+        if !start.is_zero() {
+            inner
+                .try_seek(start)
+                .map_err(|e| anyhow::anyhow!("failed to seek to the start: {e}"))?;
+        }
+        Ok(Self {
+            inner,
+            start,
+            end,
+            played_samples: 0,
+        })
+    }
+    fn samples_per_sec(&self) -> u64 {
+        self.inner.sample_rate() as u64 * self.inner.channels() as u64
+    }
+    fn remaining_samples(&self) -> Option<u64> {
+        let end = self.end?.saturating_sub(self.start);
+        let end_samples = (end.as_secs_f64() * self.samples_per_sec() as f64) as u64;
+        Some(end_samples.saturating_sub(self.played_samples))
+    }
+}
+
+impl<S: rodio::Source<Item = f32>> Iterator for Range<S> {
+    type Item = f32;
+
+    fn next(&mut self) -> Option<f32> {
+        if self.remaining_samples() == Some(0) {
+            return None;
+        }
+        self.played_samples += 1;
+        self.inner.next()
+    }
+}
+
+impl<S: rodio::Source<Item = f32>> rodio::Source for Range<S> {
+    fn current_frame_len(&self) -> Option<usize> {
+        match (self.inner.current_frame_len(), self.remaining_samples()) {
+            (Some(x), Some(y)) => Some(x.min(y as usize)),
+            (None, Some(y)) => Some(y as usize),
+            (x, None) => x,
+        }
+    }
+    fn channels(&self) -> u16 {
+        self.inner.channels()
+    }
+    fn sample_rate(&self) -> u32 {
+        self.inner.sample_rate()
+    }
+    fn total_duration(&self) -> Option<Duration> {
+        match self.end {
+            Some(end) => Some(end.saturating_sub(self.start)),
+            None => Some(self.inner.total_duration()?.saturating_sub(self.start)),
+        }
+    }
+    fn try_seek(&mut self, pos: Duration) -> Result<(), rodio::source::SeekError> {
+        self.inner.try_seek(self.start + pos)?;
+        self.played_samples = (pos.as_secs_f64() * self.samples_per_sec() as f64) as u64;
+        Ok(())
+    }
+}
+
 pub struct Source {
     title: String,
     items: Vec<(Uuid, Song)>,
+    /// The playlist file the source was read from, if it was.
+    playlist_path: Option<String>,
 }
 pub enum SourceItem<'a> {
     Title(&'a Uuid, &'a String),
@@ -96,6 +314,19 @@ pub enum SourceItem<'a> {
 
 impl Source {
     pub fn from_file(title: Option<String>, path: String) -> anyhow::Result<Self> {
+        if crate::cue::is_cue(std::path::Path::new(&path)) {
+            let sheet = crate::cue::read(std::path::Path::new(&path))?;
+            return Ok(Self {
+                title: title
+                    .or_else(|| sheet.title.clone())
+                    .unwrap_or_else(|| path.split('/').next_back().unwrap().to_owned()),
+                items: Self::cue_songs(&sheet)
+                    .into_iter()
+                    .map(|x| (Uuid::new_v4(), x))
+                    .collect(),
+                playlist_path: None,
+            });
+        }
         let song = Song::File {
             path,
             metadata: RefCell::new(None),
@@ -103,15 +334,73 @@ impl Source {
         Ok(Self {
             title: title.unwrap_or_else(|| song.get_title().unwrap().to_owned()),
             items: vec![(Uuid::new_v4(), song)],
+            playlist_path: None,
         })
     }
+    /// Expands a CUE sheet into one song per track, skipping files that don't exist.
+    fn cue_songs(sheet: &crate::cue::CueSheet) -> Vec<Song> {
+        sheet
+            .files
+            .iter()
+            .filter(|x| std::path::Path::new(&x.path).exists())
+            .flat_map(|file| {
+                file.tracks.iter().map(|track| Song::Range {
+                    path: file.path.clone(),
+                    start: track.start,
+                    end: track.end,
+                    tags: RangeTags {
+                        title: track.title.clone(),
+                        artist: track.performer.clone().or_else(|| sheet.performer.clone()),
+                        album: sheet.title.clone(),
+                        album_artist: sheet.performer.clone(),
+                        year: sheet
+                            .rem
+                            .get("DATE")
+                            .and_then(|x| x.get(..4))
+                            .and_then(|x| x.parse().ok()),
+                        track: track.number,
+                        genre: sheet.rem.get("GENRE").cloned(),
+                    },
+                    metadata: RefCell::new(None),
+                })
+            })
+            .collect()
+    }
+    /// Reads every song in a directory. Files described by a CUE sheet in the directory are
+    /// replaced by the sheet's tracks.
     pub fn from_directory(title: Option<String>, path: String) -> anyhow::Result<Self> {
         let len = std::fs::read_dir(&path)?.count();
         let mut items = Vec::with_capacity(len);
+        let mut sheets = Vec::new();
+        for entry in std::fs::read_dir(&path)? {
+            let path = entry?.path();
+            if crate::cue::is_cue(&path) {
+                // A malformed sheet is left out, and the files it refers to listed as they are
+                if let Ok(sheet) = crate::cue::read(&path) {
+                    sheets.push((path.clone(), sheet));
+                }
+            }
+        }
         for entry in std::fs::read_dir(&path)? {
             let entry = entry?;
             let path = entry.path();
-            if path.ends_with(".DS_Store") {
+            if let Some((_, sheet)) = sheets.iter().find(|(x, _)| *x == path) {
+                items.extend(
+                    Self::cue_songs(sheet)
+                        .into_iter()
+                        .map(|x| (Uuid::new_v4(), x)),
+                );
+                continue;
+            }
+            if path.ends_with(".DS_Store")
+                || crate::cue::is_cue(&path)
+                || crate::playlists::is_playlist(&path)
+                || sheets.iter().any(|(_, x)| {
+                    x.files
+                        .iter()
+                        .any(|x| std::path::Path::new(&x.path) == path)
+                })
+            {
                 continue;
             }
             items.push((
@@ -123,14 +412,127 @@ impl Source {
             ));
         }
         Ok(Self {
-            title: title.unwrap_or_else(|| path.split('/').last().unwrap().to_owned()),
+            title: title.unwrap_or_else(|| path.split('/').next_back().unwrap().to_owned()),
             items,
+            playlist_path: None,
         })
     }
+    /// Reads an M3U, PLS or XSPF playlist, picking the format by its extension.
+    pub fn from_playlist(
+        title: Option<String>,
+        path: String,
+        library: &crate::ui::Library,
+    ) -> anyhow::Result<Self> {
+        match std::path::Path::new(&path)
+            .extension()
+            .and_then(|x| x.to_str())
+            .map(|x| x.to_ascii_lowercase())
+            .as_deref()
+        {
+            Some("pls") => Self::from_pls(title, path, library),
+            Some("xspf") => Self::from_xspf(title, path, library),
+            _ => Self::from_m3u(title, path, library),
+        }
+    }
+    /// Reads an M3U/M3U8 playlist. Entries whose path does not exist are matched against the
+    /// songs already in the library by title, artist and duration.
+    pub fn from_m3u(
+        title: Option<String>,
+        path: String,
+        library: &crate::ui::Library,
+    ) -> anyhow::Result<Self> {
+        let entries = crate::m3u::read(std::path::Path::new(&path))?;
+        Ok(Self::from_entries(title, path, entries, library))
+    }
+    /// Reads a PLS playlist, see `from_m3u`.
+    pub fn from_pls(
+        title: Option<String>,
+        path: String,
+        library: &crate::ui::Library,
+    ) -> anyhow::Result<Self> {
+        let entries = crate::pls::read(std::path::Path::new(&path))?;
+        Ok(Self::from_entries(title, path, entries, library))
+    }
+    /// Reads an XSPF playlist, see `from_m3u`.
+    pub fn from_xspf(
+        title: Option<String>,
+        path: String,
+        library: &crate::ui::Library,
+    ) -> anyhow::Result<Self> {
+        let entries = crate::xspf::read(std::path::Path::new(&path))?;
+        Ok(Self::from_entries(title, path, entries, library))
+    }
+    fn from_entries(
+        title: Option<String>,
+        path: String,
+        entries: Vec<crate::playlists::Entry>,
+        library: &crate::ui::Library,
+    ) -> Self {
+        let items = entries
+            .into_iter()
+            .map(|x| {
+                // A CUE track is matched as a whole, with its range, as its path is the whole
+                // file's
+                let song = if std::path::Path::new(&x.path).exists() {
+                    library.find_song(|y| {
+                        matches!(y, Song::Range { .. }) && y.path() == x.path && x.matches(y)
+                    })
+                } else {
+                    library.find_song(|y| x.matches(y))
+                };
+                let song = song.cloned();
+                let song = song.unwrap_or(Song::File {
+                    path: x.path,
+                    metadata: RefCell::new(None),
+                });
+                (Uuid::new_v4(), song)
+            })
+            .collect();
+        Self {
+            title: title.unwrap_or_else(|| path.split('/').next_back().unwrap().to_owned()),
+            items,
+            playlist_path: Some(path),
+        }
+    }
+    pub fn playlist_path(&self) -> Option<&str> {
+        self.playlist_path.as_deref()
+    }
+    /// Gives each song the id at the same position in `ids`, the songs the playlist was saved
+    /// from, if that id belonged to `old`, so that references to them, e.g. from the playlist,
+    /// stay valid when the source is replaced. Each id is only given once, as a song can be in
+    /// the playlist twice.
+    pub fn keep_ids(&mut self, old: &Source, ids: &[Uuid]) {
+        let mut old_ids = old
+            .items
+            .iter()
+            .map(|(x, _)| *x)
+            .collect::<std::collections::HashSet<_>>();
+        for ((id, _), x) in self.items.iter_mut().zip(ids) {
+            if old_ids.remove(x) {
+                *id = *x;
+            }
+        }
+    }
+    /// Drops the cached metadata of the songs of the file at `path`.
+    pub fn forget_metadata(&mut self, path: &str) {
+        for (_, song) in self.items.iter_mut().filter(|(_, x)| x.path() == path) {
+            song.forget_metadata();
+        }
+    }
+    /// Points the songs of the file at `from` to its new path.
+    pub fn rename(&mut self, from: &str, to: &str) {
+        for (_, song) in self.items.iter_mut().filter(|(_, x)| x.path() == from) {
+            song.rename(to.to_owned());
+        }
+    }
+    /// The `i`th song, with its id.
+    pub fn song(&self, i: usize) -> Option<(&Uuid, &Song)> {
+        self.items.get(i).map(|(x, y)| (x, y))
+    }
     pub fn iter<'a>(
         &'a self,
         title_uuid: &'a Uuid,
-    ) -> impl Iterator<Item = SourceItem> + DoubleEndedIterator + '_ {
+    ) -> impl DoubleEndedIterator<Item = SourceItem<'a>> + 'a {
         std::iter::once(SourceItem::Title(title_uuid, &self.title))
             .chain(self.items.iter().map(|(x, y)| SourceItem::Song(x, y)))
     }
@@ -0,0 +1,182 @@
+use crate::config::{data_dir, WRITE_RATING_TAGS};
+use crate::song::Song;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::io::Write;
+use std::path::PathBuf;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+#[derive(Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SongStats {
+    pub plays: u32,
+    pub skips: u32,
+    /// Seconds since the Unix epoch.
+    pub last_played: Option<u64>,
+    /// 0 to 5 stars, 0 meaning unrated.
+    pub rating: u8,
+    pub loved: bool,
+}
+
+/// A song counted as played, as kept in the play log.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Play {
+    pub key: String,
+    pub title: String,
+    pub artist: String,
+    pub album: String,
+    /// Seconds since the Unix epoch.
+    pub at: u64,
+    pub duration_secs: u64,
+}
+
+impl Play {
+    /// When the song was played, in local time.
+    pub fn time(&self) -> chrono::DateTime<chrono::Local> {
+        chrono::DateTime::from_timestamp(self.at as i64, 0)
+            .unwrap_or_default()
+            .with_timezone(&chrono::Local)
+    }
+}
+
+/// How often songs were played and skipped, and how they are rated, keyed by `Song::key`, along
+/// with the log of all plays.
+pub struct Stats {
+    /// `None` if the stats couldn't be loaded, not to overwrite them.
+    path: Option<PathBuf>,
+    songs: HashMap<String, SongStats>,
+    /// One JSON `Play` per line, only ever appended to.
+    log_path: PathBuf,
+    log: Vec<Play>,
+    /// How many times the stats changed, for what depends on them to notice.
+    changes: u64,
+}
+
+pub fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs()
+}
+
+impl Stats {
+    /// Empty stats that are never saved, for when they couldn't be loaded. Plays are still
+    /// appended to the log.
+    pub fn unsaved() -> Self {
+        Self {
+            path: None,
+            songs: HashMap::new(),
+            log_path: data_dir().join("plays.jsonl"),
+            log: Vec::new(),
+            changes: 0,
+        }
+    }
+    pub fn load() -> anyhow::Result<Self> {
+        let path = data_dir().join("stats.json");
+        let songs = match std::fs::read_to_string(&path) {
+            // Entries that can't be read are skipped, and a file that can't be read at all is
+            // kept aside rather than overwritten by the next save
+            Ok(x) => match serde_json::from_str::<HashMap<String, serde_json::Value>>(&x) {
+                Ok(x) => x
+                    .into_iter()
+                    .filter_map(|(k, v)| Some((k, serde_json::from_value(v).ok()?)))
+                    .collect(),
+                Err(_) => {
+                    std::fs::copy(&path, path.with_extension("json.bak"))?;
+                    HashMap::new()
+                }
+            },
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => HashMap::new(),
+            Err(e) => return Err(e.into()),
+        };
+        let log_path = data_dir().join("plays.jsonl");
+        let log = match std::fs::read_to_string(&log_path) {
+            // A line cut short, e.g. by a crash while writing it, is skipped
+            Ok(x) => x
+                .lines()
+                .filter_map(|x| serde_json::from_str(x).ok())
+                .collect(),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Vec::new(),
+            Err(e) => return Err(e.into()),
+        };
+        Ok(Self {
+            path: Some(path),
+            songs,
+            log_path,
+            log,
+            changes: 0,
+        })
+    }
+    pub fn save(&self) -> anyhow::Result<()> {
+        let Some(path) = &self.path else {
+            return Ok(());
+        };
+        std::fs::create_dir_all(path.parent().unwrap())?;
+        std::fs::write(path, serde_json::to_string(&self.songs)?)?;
+        Ok(())
+    }
+    pub fn get(&self, key: &str) -> Option<&SongStats> {
+        self.songs.get(key)
+    }
+    pub fn changes(&self) -> u64 {
+        self.changes
+    }
+    /// Plays, oldest first.
+    pub fn log(&self) -> &[Play] {
+        &self.log
+    }
+    pub fn record_play(&mut self, song: &Song) -> anyhow::Result<()> {
+        let stats = self.songs.entry(song.key()).or_default();
+        stats.plays += 1;
+        stats.last_played = Some(now());
+        self.changes += 1;
+        self.save()?;
+        let play = Play {
+            key: song.key(),
+            title: song.get_title().unwrap_or("").to_owned(),
+            artist: song.get_artist().unwrap_or("").to_owned(),
+            album: song.get_album().unwrap_or("").to_owned(),
+            at: now(),
+            duration_secs: song.get_duration().unwrap_or_default().as_secs(),
+        };
+        let mut file = std::fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.log_path)?;
+        writeln!(file, "{}", serde_json::to_string(&play)?)?;
+        self.log.push(play);
+        Ok(())
+    }
+    pub fn record_skip(&mut self, song: &Song) -> anyhow::Result<()> {
+        self.songs.entry(song.key()).or_default().skips += 1;
+        self.changes += 1;
+        self.save()
+    }
+    /// Rates a song 0 to 5 stars, also writing the rating to the file's tags if
+    /// `WRITE_RATING_TAGS`. CUE tracks share a file, so their ratings aren't written.
+    pub fn rate(&mut self, song: &Song, rating: u8) -> anyhow::Result<()> {
+        self.songs.entry(song.key()).or_default().rating = rating.min(5);
+        self.changes += 1;
+        self.save()?;
+        if WRITE_RATING_TAGS {
+            if let Song::File { path, .. } = song {
+                crate::rating::write_tag(path, rating)?;
+            }
+        }
+        Ok(())
+    }
+    /// Moves the stats of a song to its new key, e.g. after its file was renamed.
+    pub fn rename(&mut self, from: &str, to: &str) -> anyhow::Result<()> {
+        if let Some(x) = self.songs.remove(from) {
+            self.songs.insert(to.to_owned(), x);
+        }
+        self.changes += 1;
+        self.save()
+    }
+    pub fn toggle_loved(&mut self, song: &Song) -> anyhow::Result<()> {
+        let stats = self.songs.entry(song.key()).or_default();
+        stats.loved = !stats.loved;
+        self.changes += 1;
+        self.save()
+    }
+}
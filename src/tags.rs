@@ -0,0 +1,79 @@
+use crate::song::Song;
+
+/// A tag that can be edited with the tag editor.
+#[derive(Clone, Copy, PartialEq)]
+pub enum TagField {
+    Title,
+    Artist,
+    Album,
+    AlbumArtist,
+    Year,
+    Track,
+    Genre,
+}
+
+impl TagField {
+    pub const ALL: [TagField; 7] = [
+        TagField::Title,
+        TagField::Artist,
+        TagField::Album,
+        TagField::AlbumArtist,
+        TagField::Year,
+        TagField::Track,
+        TagField::Genre,
+    ];
+    pub fn name(self) -> &'static str {
+        match self {
+            TagField::Title => "Title",
+            TagField::Artist => "Artist",
+            TagField::Album => "Album",
+            TagField::AlbumArtist => "Album artist",
+            TagField::Year => "Year",
+            TagField::Track => "Track",
+            TagField::Genre => "Genre",
+        }
+    }
+    /// The value of the tag in `song`, empty if it has none.
+    pub fn get(self, song: &Song) -> String {
+        let number = |x: Option<_>| x.map(|x: i32| x.to_string()).unwrap_or_default();
+        match self {
+            TagField::Title => song.get_title().unwrap_or("").to_owned(),
+            TagField::Artist => song.get_artist().unwrap_or("").to_owned(),
+            TagField::Album => song.get_album().unwrap_or("").to_owned(),
+            TagField::AlbumArtist => song.get_album_artist().unwrap_or("").to_owned(),
+            TagField::Year => number(song.get_year().ok().flatten()),
+            TagField::Track => number(song.get_track().ok().flatten().map(i32::from)),
+            TagField::Genre => song.get_genre().unwrap_or("").to_owned(),
+        }
+    }
+}
+
+/// Writes tags to a file, removing the ones set to an empty value.
+pub fn write(path: &str, changes: &[(TagField, String)]) -> anyhow::Result<()> {
+    let mut tag = audiotags::Tag::new().read_from_path(path)?;
+    for (field, value) in changes {
+        let value = value.trim();
+        let number = |x: &str| {
+            x.parse::<u16>()
+                .map_err(|_| anyhow::anyhow!("{}: {x} is not a number", field.name()))
+        };
+        match (field, value) {
+            (TagField::Title, "") => tag.remove_title(),
+            (TagField::Title, x) => tag.set_title(x),
+            (TagField::Artist, "") => tag.remove_artist(),
+            (TagField::Artist, x) => tag.set_artist(x),
+            (TagField::Album, "") => tag.remove_album_title(),
+            (TagField::Album, x) => tag.set_album_title(x),
+            (TagField::AlbumArtist, "") => tag.remove_album_artist(),
+            (TagField::AlbumArtist, x) => tag.set_album_artist(x),
+            (TagField::Year, "") => tag.remove_year(),
+            (TagField::Year, x) => tag.set_year(number(x)?.into()),
+            (TagField::Track, "") => tag.remove_track_number(),
+            (TagField::Track, x) => tag.set_track_number(number(x)?),
+            (TagField::Genre, "") => tag.remove_genre(),
+            (TagField::Genre, x) => tag.set_genre(x),
+        }
+    }
+    tag.write_to_path(path)?;
+    Ok(())
+}
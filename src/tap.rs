@@ -0,0 +1,127 @@
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+/// How many samples are kept, enough for the largest FFT of stereo audio.
+const CAPACITY: usize = 8192;
+/// How many samples are collected before handing them over, so that the audio thread rarely locks.
+const BATCH: usize = 512;
+
+/// The latest samples played, interleaved, shared between the audio thread and the UI.
+pub struct Samples {
+    pub samples: VecDeque<f32>,
+    pub channels: u16,
+    pub sample_rate: u32,
+    /// The highest absolute value of each channel since `take_peaks`, so that no peak is missed
+    /// between frames, however long.
+    peaks: Vec<f32>,
+}
+
+impl Samples {
+    pub fn new() -> Self {
+        Self {
+            samples: VecDeque::with_capacity(CAPACITY),
+            channels: 2,
+            sample_rate: 44100,
+            peaks: Vec::new(),
+        }
+    }
+    /// The latest `n` frames, averaged across channels, oldest first.
+    pub fn mono(&self, n: usize) -> Vec<f32> {
+        let channels = self.channels.max(1) as usize;
+        let frames = self.samples.len() / channels;
+        let skip = frames.saturating_sub(n) * channels;
+        let samples = self.samples.iter().skip(skip).copied().collect::<Vec<_>>();
+        samples
+            .chunks_exact(channels)
+            .map(|x| x.iter().sum::<f32>() / channels as f32)
+            .collect()
+    }
+    /// The root mean square of each channel over the latest `n` frames.
+    pub fn rms(&self, n: usize) -> Vec<f32> {
+        let channels = self.channels.max(1) as usize;
+        let frames = (self.samples.len() / channels).min(n);
+        let skip = self.samples.len() - frames * channels;
+        let mut sums = vec![0f32; channels];
+        for (i, x) in self.samples.iter().skip(skip).enumerate() {
+            sums[i % channels] += x * x;
+        }
+        sums.into_iter()
+            .map(|x| (x / frames.max(1) as f32).sqrt())
+            .collect()
+    }
+    /// The peak of each channel since it was last called.
+    pub fn take_peaks(&mut self) -> Vec<f32> {
+        let channels = self.channels.max(1) as usize;
+        let mut peaks = std::mem::take(&mut self.peaks);
+        peaks.resize(channels, 0.);
+        peaks
+    }
+}
+
+/// Passes samples through, copying them to `Samples` on the way.
+pub struct Tap<S> {
+    inner: S,
+    shared: Arc<Mutex<Samples>>,
+    batch: Vec<f32>,
+}
+
+impl<S: rodio::Source<Item = f32>> Tap<S> {
+    pub fn new(inner: S, shared: Arc<Mutex<Samples>>) -> Self {
+        Self {
+            inner,
+            shared,
+            batch: Vec::with_capacity(BATCH),
+        }
+    }
+}
+
+impl<S: rodio::Source<Item = f32>> Iterator for Tap<S> {
+    type Item = f32;
+
+    fn next(&mut self) -> Option<f32> {
+        let sample = self.inner.next()?;
+        self.batch.push(sample);
+        // Whole frames only, to keep the channels apart
+        if self.batch.len() >= BATCH
+            && self.batch.len() % self.inner.channels().max(1) as usize == 0
+        {
+            // Rather than waiting for the UI, the batch is dropped if it is busy
+            if let Ok(mut shared) = self.shared.try_lock() {
+                shared.channels = self.inner.channels();
+                shared.sample_rate = self.inner.sample_rate();
+                let channels = self.inner.channels().max(1) as usize;
+                if shared.peaks.len() != channels {
+                    shared.peaks = vec![0.; channels];
+                }
+                for (i, x) in self.batch.iter().enumerate() {
+                    let peak = &mut shared.peaks[i % channels];
+                    *peak = peak.max(x.abs());
+                }
+                shared.samples.extend(self.batch.iter());
+                let excess = shared.samples.len().saturating_sub(CAPACITY);
+                shared.samples.drain(..excess);
+            }
+            self.batch.clear();
+        }
+        Some(sample)
+    }
+}
+
+impl<S: rodio::Source<Item = f32>> rodio::Source for Tap<S> {
+    fn current_frame_len(&self) -> Option<usize> {
+        self.inner.current_frame_len()
+    }
+    fn channels(&self) -> u16 {
+        self.inner.channels()
+    }
+    fn sample_rate(&self) -> u32 {
+        self.inner.sample_rate()
+    }
+    fn total_duration(&self) -> Option<Duration> {
+        self.inner.total_duration()
+    }
+    fn try_seek(&mut self, pos: Duration) -> Result<(), rodio::source::SeekError> {
+        self.inner.try_seek(pos)
+    }
+}
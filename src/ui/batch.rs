@@ -0,0 +1,64 @@
+use crate::naming::Plan;
+use crate::ui::{UiComponent, UiEvent, UiEventResult};
+use ratatui::prelude::*;
+use ratatui::widgets::{Block, BorderType, Borders, Clear, Paragraph};
+
+/// A popup previewing what a batch tool is about to do to each file, before it does it.
+pub struct BatchPreview {
+    title: String,
+    pub plan: Plan,
+    scroll: u16,
+}
+
+impl BatchPreview {
+    pub fn new(title: String, plan: Plan) -> Self {
+        Self {
+            title,
+            plan,
+            scroll: 0,
+        }
+    }
+}
+
+impl UiComponent for BatchPreview {
+    fn handle_event(&mut self, event: UiEvent) -> UiEventResult {
+        use crossterm::event::KeyCode as C;
+        match event {
+            UiEvent::Key(C::Char('j') | C::Down) => self.scroll = self.scroll.saturating_add(1),
+            UiEvent::Key(C::Char('k') | C::Up) => self.scroll = self.scroll.saturating_sub(1),
+            // Enter applies the plan and Esc cancels it
+            UiEvent::Key(C::Enter | C::Esc) => return UiEventResult::PassThrough,
+            UiEvent::Key(_) => {}
+            _ => return UiEventResult::PassThrough,
+        }
+        UiEventResult::Handled
+    }
+    /// Draws the popup over `area`.
+    fn render(&self, frame: &mut Frame, area: Rect) {
+        let mut lines = Vec::new();
+        for (path, x) in self.plan.describe() {
+            lines.push(Line::styled(path, Style::new().bold()));
+            lines.push(match x {
+                Ok(x) => Line::styled(format!("  {x}"), Style::new().fg(Color::Green)),
+                Err(e) => Line::styled(format!("  skipped: {e}"), Style::new().fg(Color::LightRed)),
+            });
+        }
+        lines.push(Line::styled(
+            "Enter: apply, Esc: cancel",
+            Style::new().dim(),
+        ));
+        frame.render_widget(Clear, area);
+        frame.render_widget(
+            Paragraph::new(lines)
+                .block(
+                    Block::new()
+                        .borders(Borders::all())
+                        .border_type(BorderType::Rounded)
+                        .title(self.title.as_str())
+                        .title_alignment(Alignment::Center),
+                )
+                .scroll((self.scroll, 0)),
+            area,
+        );
+    }
+}
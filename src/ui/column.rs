@@ -0,0 +1,141 @@
+use crate::song::Song;
+use crate::stats::Stats;
+use serde::{Deserialize, Serialize};
+use std::borrow::Cow;
+use std::cmp::Ordering;
+
+/// A column of the library table.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Serialize, Deserialize)]
+pub enum Column {
+    /// The position of the row, also marking songs with chapters.
+    Index,
+    /// The title, prefixed with the artist unless the `Artist` column is shown. Artists, albums,
+    /// source titles and chapters are shown in this column too.
+    Title,
+    Artist,
+    Album,
+    Year,
+    Genre,
+    Bitrate,
+    Duration,
+    Path,
+    Plays,
+    Skips,
+    LastPlayed,
+    /// Stars, and a heart for loved songs.
+    Rating,
+}
+
+impl Column {
+    pub const ALL: [Column; 13] = [
+        Column::Index,
+        Column::Title,
+        Column::Artist,
+        Column::Album,
+        Column::Year,
+        Column::Genre,
+        Column::Bitrate,
+        Column::Duration,
+        Column::Path,
+        Column::Plays,
+        Column::Skips,
+        Column::LastPlayed,
+        Column::Rating,
+    ];
+    pub fn header(self) -> &'static str {
+        match self {
+            Column::Index => "  #",
+            Column::Title => "Title",
+            Column::Artist => "Artist",
+            Column::Album => "Album",
+            Column::Year => "Year",
+            Column::Genre => "Genre",
+            Column::Bitrate => "kbps",
+            Column::Duration => "Duration",
+            Column::Path => "Path",
+            Column::Plays => "Plays",
+            Column::Skips => "Skips",
+            Column::LastPlayed => "Last played",
+            Column::Rating => "Rating",
+        }
+    }
+    /// The content of the column for a song, except for `Index` and `Title` which depend on the
+    /// rest of the table.
+    pub fn text<'a>(self, song: &'a Song, stats: &Stats) -> Cow<'a, str> {
+        let song_stats = stats.get(&song.key()).cloned().unwrap_or_default();
+        match self {
+            Column::Index | Column::Title => Cow::Borrowed(""),
+            Column::Artist => Cow::Borrowed(song.get_artist().unwrap_or("")),
+            Column::Album => Cow::Borrowed(song.get_album().unwrap_or("")),
+            Column::Year => match song.get_year() {
+                Ok(Some(x)) => Cow::Owned(x.to_string()),
+                _ => Cow::Borrowed(""),
+            },
+            Column::Genre => Cow::Borrowed(song.get_genre().unwrap_or("")),
+            Column::Bitrate => match song.get_bitrate() {
+                Ok(Some(x)) => Cow::Owned(format!("{x:4}")),
+                _ => Cow::Borrowed(""),
+            },
+            Column::Duration => match song.get_duration() {
+                Ok(x) => Cow::Owned(format!("{:02}:{:02}", x.as_secs() / 60, x.as_secs() % 60)),
+                Err(_) => Cow::Borrowed("--:--"),
+            },
+            Column::Path => Cow::Borrowed(song.path()),
+            Column::Plays => Cow::Owned(format!("{:5}", song_stats.plays)),
+            Column::Skips => Cow::Owned(format!("{:5}", song_stats.skips)),
+            Column::LastPlayed => match song_stats
+                .last_played
+                .and_then(|x| chrono::DateTime::from_timestamp(x as i64, 0))
+            {
+                Some(x) => Cow::Owned(
+                    x.with_timezone(&chrono::Local)
+                        .format("%Y-%m-%d %H:%M")
+                        .to_string(),
+                ),
+                None => Cow::Borrowed(""),
+            },
+            Column::Rating => Cow::Owned(format!(
+                "{}{}{}",
+                "★".repeat(song_stats.rating as usize),
+                "☆".repeat(5 - song_stats.rating.min(5) as usize),
+                if song_stats.loved { " ♥" } else { "" }
+            )),
+        }
+    }
+    /// Compares two songs by this column. Strings are compared case-insensitively, and songs
+    /// without a value come last.
+    pub fn compare(self, a: &Song, b: &Song, stats: &Stats) -> Ordering {
+        fn last<T: Ord>(a: Option<T>, b: Option<T>) -> Ordering {
+            match (a, b) {
+                (Some(a), Some(b)) => a.cmp(&b),
+                (Some(_), None) => Ordering::Less,
+                (None, Some(_)) => Ordering::Greater,
+                (None, None) => Ordering::Equal,
+            }
+        }
+        let text =
+            |x: anyhow::Result<&str>| x.ok().filter(|x| !x.is_empty()).map(|x| x.to_lowercase());
+        let stats = |x: &Song| stats.get(&x.key()).cloned().unwrap_or_default();
+        match self {
+            Column::Index => Ordering::Equal,
+            Column::Title => last(text(a.get_title()), text(b.get_title())),
+            Column::Artist => last(text(a.get_artist()), text(b.get_artist())),
+            Column::Album => last(text(a.get_album()), text(b.get_album())),
+            Column::Year => last(a.get_year().ok().flatten(), b.get_year().ok().flatten()),
+            Column::Genre => last(text(a.get_genre()), text(b.get_genre())),
+            Column::Bitrate => last(
+                a.get_bitrate().ok().flatten(),
+                b.get_bitrate().ok().flatten(),
+            ),
+            Column::Duration => last(a.get_duration().ok(), b.get_duration().ok()),
+            Column::Path => a.path().cmp(b.path()),
+            Column::Plays => stats(a).plays.cmp(&stats(b).plays),
+            Column::Skips => stats(a).skips.cmp(&stats(b).skips),
+            Column::LastPlayed => last(stats(a).last_played, stats(b).last_played),
+            Column::Rating => {
+                let (a, b) = (stats(a), stats(b));
+                (a.rating, a.loved).cmp(&(b.rating, b.loved))
+            }
+        }
+    }
+}
@@ -0,0 +1,294 @@
+use crate::ui::{Playlist, UiComponent, UiEvent, UiEventResult};
+use base64::Engine;
+use image::imageops::FilterType;
+use image::{DynamicImage, RgbImage};
+use ratatui::prelude::*;
+use ratatui::widgets::{Block, BorderType, Borders};
+use std::cell::RefCell;
+use std::io::Write;
+use std::rc::Rc;
+use std::sync::mpsc::Receiver;
+
+/// How images are drawn in the terminal.
+#[derive(Clone, Copy, PartialEq)]
+enum Protocol {
+    /// `▀` with the upper pixel as the foreground and the lower one as the background, two pixels
+    /// per cell, in any terminal with true colors.
+    HalfBlocks,
+    Kitty,
+    Sixel,
+}
+
+impl Protocol {
+    /// Guesses what the terminal supports from the environment.
+    fn detect() -> Self {
+        let var = |x| std::env::var(x).unwrap_or_default();
+        let (term, program) = (var("TERM"), var("TERM_PROGRAM"));
+        if term.contains("kitty")
+            || std::env::var_os("KITTY_WINDOW_ID").is_some()
+            || ["WezTerm", "ghostty"].contains(&program.as_str())
+        {
+            Protocol::Kitty
+        } else if term.contains("sixel")
+            || term.starts_with("foot")
+            || term.starts_with("mlterm")
+            || ["mlterm", "contour"].contains(&program.as_str())
+        {
+            Protocol::Sixel
+        } else {
+            Protocol::HalfBlocks
+        }
+    }
+}
+
+/// The size of a cell in pixels, guessing if the terminal doesn't tell.
+fn cell_size() -> (u32, u32) {
+    match crossterm::terminal::window_size() {
+        Ok(x) if x.width > 0 && x.columns > 0 && x.rows > 0 => {
+            ((x.width / x.columns) as u32, (x.height / x.rows) as u32)
+        }
+        _ => (10, 20),
+    }
+}
+
+/// Encodes an image as sixels, with colors reduced to a 6×6×6 cube.
+fn sixel(image: &RgbImage) -> Vec<u8> {
+    let (width, height) = image.dimensions();
+    let index = |x, y| {
+        let [r, g, b] = image.get_pixel(x, y).0;
+        (r as usize * 6 / 256) * 36 + (g as usize * 6 / 256) * 6 + b as usize * 6 / 256
+    };
+    let mut out = format!("\x1bPq\"1;1;{width};{height}").into_bytes();
+    for i in 0..216 {
+        let level = |x| x * 100 / 5;
+        let _ = write!(
+            out,
+            "#{i};2;{};{};{}",
+            level(i / 36),
+            level(i / 6 % 6),
+            level(i % 6)
+        );
+    }
+    for band in (0..height).step_by(6) {
+        let rows = band..(band + 6).min(height);
+        let mut colors = (0..width)
+            .flat_map(|x| rows.clone().map(move |y| (x, y)))
+            .map(|(x, y)| index(x, y))
+            .collect::<Vec<_>>();
+        colors.sort_unstable();
+        colors.dedup();
+        for color in colors {
+            let _ = write!(out, "#{color}");
+            let sixels = (0..width).map(|x| {
+                let bits = rows
+                    .clone()
+                    .filter(|y| index(x, *y) == color)
+                    .fold(0, |bits, y| bits | 1 << (y - band));
+                (63 + bits) as u8 as char
+            });
+            // Run-length encoded
+            let mut run: Option<(char, usize)> = None;
+            for c in sixels.chain(std::iter::once('\0')) {
+                match run {
+                    Some((x, n)) if x == c => run = Some((x, n + 1)),
+                    _ => {
+                        match run {
+                            Some((x, n)) if n > 3 => {
+                                let _ = write!(out, "!{n}{x}");
+                            }
+                            Some((x, n)) => out.extend(std::iter::repeat_n(x as u8, n)),
+                            None => {}
+                        }
+                        run = Some((c, 1));
+                    }
+                }
+            }
+            out.push(b'$');
+        }
+        out.push(b'-');
+    }
+    out.extend(b"\x1b\\");
+    out
+}
+
+/// Transmits and shows a PNG with the kitty graphics protocol, in chunks as required.
+fn kitty(image: &RgbImage, columns: u16, rows: u16) -> Vec<u8> {
+    let mut png = Vec::new();
+    let _ = image.write_to(
+        &mut std::io::Cursor::new(&mut png),
+        image::ImageOutputFormat::Png,
+    );
+    let data = base64::engine::general_purpose::STANDARD.encode(png);
+    let chunks = data.as_bytes().chunks(4096).collect::<Vec<_>>();
+    let mut out = Vec::new();
+    for (i, chunk) in chunks.iter().enumerate() {
+        let more = (i + 1 < chunks.len()) as u8;
+        if i == 0 {
+            let _ = write!(
+                out,
+                "\x1b_Ga=T,f=100,t=d,c={columns},r={rows},C=1,q=2,m={more};"
+            );
+        } else {
+            let _ = write!(out, "\x1b_Gm={more};");
+        }
+        out.extend(*chunk);
+        out.extend(b"\x1b\\");
+    }
+    out
+}
+
+/// Album art of the playing song.
+pub struct Cover {
+    playlist: Option<Rc<RefCell<Playlist>>>,
+    protocol: Protocol,
+    /// The key of the song whose cover is loaded, and its cover, if it has one.
+    loaded: RefCell<Option<(String, Option<DynamicImage>)>>,
+    /// The key of the song whose cover is being loaded in the background, and where it arrives.
+    loading: RefCell<Option<(String, Receiver<Option<DynamicImage>>)>>,
+    /// The cover scaled for an area, as it was last drawn.
+    scaled: RefCell<Option<(Rect, RgbImage)>>,
+    /// Where the cover should be drawn with a graphics protocol in this frame, and where it is.
+    wanted: RefCell<Option<(String, Rect)>>,
+    shown: Option<(String, Rect)>,
+}
+
+impl Cover {
+    pub fn new() -> Self {
+        Self {
+            playlist: None,
+            protocol: Protocol::detect(),
+            loaded: RefCell::new(None),
+            loading: RefCell::new(None),
+            scaled: RefCell::new(None),
+            wanted: RefCell::new(None),
+            shown: None,
+        }
+    }
+    pub fn set_ref_to_playlist(&mut self, playlist: Rc<RefCell<Playlist>>) {
+        self.playlist = Some(playlist);
+    }
+    /// Whether the playing song has a cover, starting to load it in the background if it wasn't
+    /// yet. Until it is loaded, the song has none.
+    pub fn has_cover(&self) -> bool {
+        let Some(song) = self.playlist.as_ref().unwrap().borrow().playing() else {
+            return false;
+        };
+        let key = song.key();
+        let mut loaded = self.loaded.borrow_mut();
+        if loaded.as_ref().map(|(x, _)| x) != Some(&key) {
+            let mut loading = self.loading.borrow_mut();
+            if loading.as_ref().map(|(x, _)| x) != Some(&key) {
+                let (tx, rx) = std::sync::mpsc::channel();
+                std::thread::spawn(move || {
+                    let _ = tx.send(crate::cover::load(&song));
+                });
+                *loading = Some((key.clone(), rx));
+            }
+            let (_, rx) = loading.as_ref().unwrap();
+            match rx.try_recv() {
+                Ok(image) => {
+                    *loaded = Some((key, image));
+                    *loading = None;
+                }
+                Err(_) => *loaded = None,
+            }
+            *self.scaled.borrow_mut() = None;
+        }
+        loaded.as_ref().is_some_and(|(_, x)| x.is_some())
+    }
+    /// The cover scaled to fit `area` in pixels, keeping its aspect ratio.
+    fn scaled(&self, area: Rect, pixels: (u32, u32)) -> Option<RgbImage> {
+        let mut scaled = self.scaled.borrow_mut();
+        if scaled.as_ref().map(|(x, _)| *x) != Some(area) {
+            let loaded = self.loaded.borrow();
+            let image = loaded.as_ref()?.1.as_ref()?;
+            let image = image.resize(pixels.0, pixels.1, FilterType::Triangle);
+            *scaled = Some((area, image.to_rgb8()));
+        }
+        scaled.as_ref().map(|(_, x)| x.clone())
+    }
+    /// Draws the cover with the graphics protocol, if there is one, once the frame is drawn:
+    /// only when it changed, as it stays until erased. Returns whether the screen has to be
+    /// redrawn entirely, to erase an old cover.
+    pub fn draw_graphics(&mut self) -> anyhow::Result<bool> {
+        let wanted = self.wanted.take();
+        if self.protocol == Protocol::HalfBlocks || wanted == self.shown {
+            return Ok(false);
+        }
+        let mut stderr = std::io::stderr();
+        let mut redraw = false;
+        if self.shown.take().is_some() {
+            match self.protocol {
+                Protocol::Kitty => write!(stderr, "\x1b_Ga=d,d=A,q=2\x1b\\")?,
+                // Sixels are pixels on the screen, only erased by drawing over them
+                _ => redraw = true,
+            }
+        }
+        if let (false, Some((key, area))) = (redraw, wanted) {
+            let (width, height) = cell_size();
+            let pixels = (area.width as u32 * width, area.height as u32 * height);
+            if let Some(image) = self.scaled(area, pixels) {
+                // Centered in the area
+                let columns = (image.width() as f64 / width as f64).ceil() as u16;
+                let rows = (image.height() as f64 / height as f64).ceil() as u16;
+                let x = area.x + area.width.saturating_sub(columns) / 2;
+                let y = area.y + area.height.saturating_sub(rows) / 2;
+                crossterm::queue!(stderr, crossterm::cursor::MoveTo(x, y))?;
+                stderr.write_all(&match self.protocol {
+                    Protocol::Kitty => kitty(&image, columns, rows),
+                    _ => sixel(&image),
+                })?;
+            }
+            self.shown = Some((key, area));
+        }
+        stderr.flush()?;
+        Ok(redraw)
+    }
+}
+
+impl UiComponent for Cover {
+    fn handle_event(&mut self, _event: UiEvent) -> UiEventResult {
+        UiEventResult::PassThrough
+    }
+    fn render(&self, frame: &mut Frame, area: Rect) {
+        let loaded = self.loaded.borrow();
+        let Some((key, Some(_))) = loaded.as_ref() else {
+            return;
+        };
+        let block = Block::new()
+            .borders(Borders::all())
+            .border_type(BorderType::Rounded)
+            .title("Cover")
+            .title_alignment(Alignment::Center);
+        let inner = block.inner(area);
+        frame.render_widget(block, area);
+        if inner.is_empty() {
+            return;
+        }
+        if self.protocol != Protocol::HalfBlocks {
+            *self.wanted.borrow_mut() = Some((key.clone(), inner));
+            return;
+        }
+        let pixels = (inner.width as u32, inner.height as u32 * 2);
+        drop(loaded);
+        let Some(image) = self.scaled(inner, pixels) else {
+            return;
+        };
+        let x0 = inner.x + (inner.width - image.width() as u16) / 2;
+        let y0 = inner.y + (inner.height - (image.height() as u16).div_ceil(2)) / 2;
+        let buffer = frame.buffer_mut();
+        for y in (0..image.height()).step_by(2) {
+            for x in 0..image.width() {
+                let color = |y| {
+                    let [r, g, b] = image.get_pixel(x, y).0;
+                    Color::Rgb(r, g, b)
+                };
+                let cell = buffer.get_mut(x0 + x as u16, y0 + y as u16 / 2);
+                cell.set_char('▀').set_fg(color(y));
+                if y + 1 < image.height() {
+                    cell.set_bg(color(y + 1));
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,285 @@
+use crate::stats::{now, Play, Stats};
+use crate::ui::{Library, ScrollStatus, UiComponent, UiEvent, UiEventResult};
+use ratatui::prelude::*;
+use ratatui::widgets::{Block, BorderType, Borders, List, ListItem, Paragraph};
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::rc::Rc;
+use std::time::Duration;
+
+/// How far back the top artists, albums and tracks look.
+#[derive(Clone, Copy, PartialEq)]
+enum Period {
+    Week,
+    Month,
+    Year,
+    AllTime,
+}
+
+impl Period {
+    fn days(self) -> Option<u64> {
+        match self {
+            Period::Week => Some(7),
+            Period::Month => Some(30),
+            Period::Year => Some(365),
+            Period::AllTime => None,
+        }
+    }
+    fn next(self) -> Self {
+        match self {
+            Period::Week => Period::Month,
+            Period::Month => Period::Year,
+            Period::Year => Period::AllTime,
+            Period::AllTime => Period::Week,
+        }
+    }
+    fn name(self) -> &'static str {
+        match self {
+            Period::Week => "last 7 days",
+            Period::Month => "last 30 days",
+            Period::Year => "last 365 days",
+            Period::AllTime => "all time",
+        }
+    }
+}
+
+/// What the dashboard shows, computed from the whole play log and library.
+struct Aggregates {
+    /// What they were computed for: `Library::changes`, `Stats::changes`, the period and the
+    /// minute, as the period moves with time.
+    key: (u64, u64, Period, u64),
+    /// Songs in the library and their total duration.
+    library: (usize, Duration),
+    /// Plays and seconds listened in the period and of all time.
+    period: (usize, u64),
+    all_time: (usize, u64),
+    /// Top artists, albums and tracks of the period.
+    tops: [Vec<(String, usize)>; 3],
+    history: Vec<Line<'static>>,
+}
+
+/// Listening history by day and statistics from the play log, shown in place of the library.
+pub struct Dashboard {
+    library: Option<Rc<RefCell<Library>>>,
+    stats: Option<Rc<RefCell<Stats>>>,
+    period: Period,
+    focused: bool,
+    viewpoint: RefCell<ScrollStatus>,
+    /// Computed again only when what they depend on changes, not every frame.
+    aggregates: RefCell<Option<Aggregates>>,
+}
+
+fn hours_minutes(secs: u64) -> String {
+    format!("{}h{:02}m", secs / 3600, secs / 60 % 60)
+}
+
+/// Counts plays by `key`, most played first.
+fn top<'a>(plays: &[&'a Play], key: impl Fn(&'a Play) -> String) -> Vec<(String, usize)> {
+    let mut counts = HashMap::<String, usize>::new();
+    for play in plays {
+        *counts.entry(key(play)).or_default() += 1;
+    }
+    let mut counts = counts.into_iter().collect::<Vec<_>>();
+    counts.sort_by(|(a, x), (b, y)| y.cmp(x).then_with(|| a.cmp(b)));
+    counts
+}
+
+impl Dashboard {
+    pub fn new() -> Self {
+        Self {
+            library: None,
+            stats: None,
+            period: Period::Week,
+            focused: false,
+            viewpoint: RefCell::new(ScrollStatus {
+                steps: 0,
+                selected: 0,
+                offset: 0,
+            }),
+            aggregates: RefCell::new(None),
+        }
+    }
+    pub fn set_ref_to_library(&mut self, library: Rc<RefCell<Library>>) {
+        self.library = Some(library);
+    }
+    pub fn set_ref_to_stats(&mut self, stats: Rc<RefCell<Stats>>) {
+        self.stats = Some(stats);
+    }
+    /// The lines of the history: a heading for each day, followed by its plays, latest first.
+    fn history(&self, log: &[Play]) -> Vec<Line<'static>> {
+        let mut lines = Vec::new();
+        let mut day = None;
+        for (i, play) in log.iter().enumerate().rev() {
+            let time = play.time();
+            if day != Some(time.date_naive()) {
+                day = Some(time.date_naive());
+                let plays = log[..=i]
+                    .iter()
+                    .rev()
+                    .take_while(|x| x.time().date_naive() == time.date_naive())
+                    .collect::<Vec<_>>();
+                lines.push(Line::styled(
+                    format!(
+                        "{}  {} plays, {}",
+                        time.format("%Y-%m-%d %a"),
+                        plays.len(),
+                        hours_minutes(plays.iter().map(|x| x.duration_secs).sum())
+                    ),
+                    Style::new().bold(),
+                ));
+            }
+            lines.push(Line::from(format!(
+                "  {}  {} - {}",
+                time.format("%H:%M"),
+                play.artist,
+                play.title
+            )));
+        }
+        lines
+    }
+    /// Computes the aggregates again if what they depend on changed.
+    fn update_aggregates(&self) {
+        let stats = self.stats.as_ref().unwrap().borrow();
+        let library = self.library.as_ref().unwrap().borrow();
+        let key = (library.changes(), stats.changes(), self.period, now() / 60);
+        let mut aggregates = self.aggregates.borrow_mut();
+        if aggregates.as_ref().is_some_and(|x| x.key == key) {
+            return;
+        }
+        let log = stats.log();
+        let since = self
+            .period
+            .days()
+            .map_or(0, |x| now().saturating_sub(x * 24 * 60 * 60));
+        let plays = log.iter().filter(|x| x.at >= since).collect::<Vec<_>>();
+        *aggregates = Some(Aggregates {
+            key,
+            library: library.songs().fold((0, Duration::ZERO), |(n, d), (_, s)| {
+                (n + 1, d + s.get_duration().unwrap_or_default())
+            }),
+            period: (plays.len(), plays.iter().map(|x| x.duration_secs).sum()),
+            all_time: (log.len(), log.iter().map(|x| x.duration_secs).sum()),
+            tops: [
+                top(&plays, |x| x.artist.clone()),
+                top(&plays, |x| format!("{} - {}", x.artist, x.album)),
+                top(&plays, |x| format!("{} - {}", x.artist, x.title)),
+            ],
+            history: self.history(log),
+        });
+    }
+}
+
+impl UiComponent for Dashboard {
+    fn handle_event(&mut self, event: UiEvent) -> UiEventResult {
+        use crossterm::event::KeyCode as C;
+        match event {
+            UiEvent::Key(C::Char('j')) => {
+                self.viewpoint.borrow_mut().steps += 1;
+                UiEventResult::Handled
+            }
+            UiEvent::Key(C::Char('k')) => {
+                self.viewpoint.borrow_mut().steps -= 1;
+                UiEventResult::Handled
+            }
+            UiEvent::Key(C::Char('p')) => {
+                self.period = self.period.next();
+                UiEventResult::Handled
+            }
+            UiEvent::FocusGained => {
+                self.focused = true;
+                UiEventResult::Handled
+            }
+            UiEvent::FocusLost => {
+                self.focused = false;
+                UiEventResult::Handled
+            }
+            _ => UiEventResult::PassThrough,
+        }
+    }
+    fn render(&self, frame: &mut Frame, area: Rect) {
+        let block = Block::new()
+            .borders(Borders::all())
+            .border_type(BorderType::Rounded)
+            .title("Statistics")
+            .title_style(Style::default().fg(if self.focused {
+                Color::Blue
+            } else {
+                Color::Reset
+            }))
+            .title_alignment(Alignment::Center);
+        let inner = block.inner(area);
+        frame.render_widget(block, area);
+        let [summary, tops, history] = *Layout::default()
+            .direction(Direction::Vertical)
+            .constraints([
+                Constraint::Length(3),
+                Constraint::Length(12),
+                Constraint::Min(0),
+            ])
+            .split(inner)
+        else {
+            unreachable!()
+        };
+
+        self.update_aggregates();
+        let aggregates = self.aggregates.borrow();
+        let aggregates = aggregates.as_ref().unwrap();
+        let (songs, duration) = aggregates.library;
+        frame.render_widget(
+            Paragraph::new(vec![
+                Line::from(format!(
+                    "Library: {songs} songs, {}",
+                    hours_minutes(duration.as_secs())
+                )),
+                Line::from(format!(
+                    "Listened ({}): {} plays, {}  ·  all time: {} plays, {}",
+                    self.period.name(),
+                    aggregates.period.0,
+                    hours_minutes(aggregates.period.1),
+                    aggregates.all_time.0,
+                    hours_minutes(aggregates.all_time.1)
+                )),
+                Line::styled("p: change period", Style::new().dim()),
+            ]),
+            summary,
+        );
+
+        let columns = Layout::default()
+            .direction(Direction::Horizontal)
+            .constraints([Constraint::Ratio(1, 3); 3])
+            .split(tops);
+        let titles = ["Top artists", "Top albums", "Top tracks"];
+        for ((title, counts), area) in titles.into_iter().zip(&aggregates.tops).zip(columns.iter())
+        {
+            let items = counts
+                .iter()
+                .take(area.height.saturating_sub(2) as usize)
+                .map(|(x, n)| ListItem::new(format!("{n:4} {x}")))
+                .collect::<Vec<_>>();
+            frame.render_widget(
+                List::new(items).block(Block::new().borders(Borders::TOP).title(title)),
+                *area,
+            );
+        }
+
+        let block = Block::new().borders(Borders::TOP).title("History");
+        let height = block.inner(history).height as usize;
+        let lines = &aggregates.history;
+        let mut viewpoint = self.viewpoint.borrow_mut();
+        viewpoint.calculate_steps(lines.len(), height);
+        let lines = lines
+            .iter()
+            .enumerate()
+            .skip(viewpoint.selected - viewpoint.offset)
+            .take(height)
+            .map(|(i, x)| {
+                let mut x = x.clone();
+                if i == viewpoint.selected && self.focused {
+                    x.patch_style(Style::new().fg(Color::Blue));
+                }
+                x
+            })
+            .collect::<Vec<_>>();
+        frame.render_widget(Paragraph::new(lines).block(block), history);
+    }
+}
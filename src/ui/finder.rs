@@ -0,0 +1,128 @@
+use crate::config::FINDER_RESULTS;
+use crate::song::SourceItem;
+use crate::ui::Library;
+use fuzzy_matcher::skim::SkimMatcherV2;
+use fuzzy_matcher::FuzzyMatcher;
+use ratatui::prelude::*;
+use ratatui::widgets::{Block, BorderType, Borders, Clear, List, ListItem};
+use std::cell::RefCell;
+use std::rc::Rc;
+use uuid::Uuid;
+
+/// A popup listing the songs that fuzzy match what is typed in the find prompt, best first.
+pub struct Finder {
+    library: Option<Rc<RefCell<Library>>>,
+    pattern: String,
+    results: Vec<Uuid>,
+    selected: usize,
+}
+
+impl Finder {
+    pub fn new() -> Self {
+        Self {
+            library: None,
+            pattern: String::new(),
+            results: Vec::new(),
+            selected: 0,
+        }
+    }
+    pub fn set_ref_to_library(&mut self, library: Rc<RefCell<Library>>) {
+        self.library = Some(library);
+    }
+    /// Ranks the songs of the library against `pattern`, matching their artist, title, album and
+    /// path. Does nothing if the pattern didn't change.
+    pub fn update(&mut self, pattern: &str) {
+        if pattern == self.pattern {
+            return;
+        }
+        self.pattern = pattern.to_owned();
+        self.selected = 0;
+        self.results.clear();
+        if pattern.trim().is_empty() {
+            return;
+        }
+        let matcher = SkimMatcherV2::default().ignore_case();
+        let library = self.library.as_ref().unwrap().borrow();
+        let mut results = library
+            .songs()
+            .filter_map(|(id, s)| {
+                let text = format!(
+                    "{} {} {} {}",
+                    s.get_artist().unwrap_or(""),
+                    s.get_title().unwrap_or(""),
+                    s.get_album().unwrap_or(""),
+                    s.path()
+                );
+                Some((matcher.fuzzy_match(&text, pattern.trim())?, *id))
+            })
+            .collect::<Vec<_>>();
+        // Stable, so equally good matches keep the library's order
+        results.sort_by_key(|(score, _)| std::cmp::Reverse(*score));
+        self.results = results
+            .into_iter()
+            .take(FINDER_RESULTS)
+            .map(|(_, x)| x)
+            .collect();
+    }
+    pub fn clear(&mut self) {
+        self.update("");
+    }
+    pub fn next(&mut self) {
+        if !self.results.is_empty() {
+            self.selected = (self.selected + 1) % self.results.len();
+        }
+    }
+    pub fn prev(&mut self) {
+        if !self.results.is_empty() {
+            self.selected = (self.selected + self.results.len() - 1) % self.results.len();
+        }
+    }
+    /// The highlighted result, the best match unless another one was picked.
+    pub fn selected(&self) -> Option<Uuid> {
+        self.results.get(self.selected).copied()
+    }
+    /// Draws the popup over the bottom of `area`, if there are any results.
+    pub fn render(&self, frame: &mut Frame, area: Rect) {
+        if self.results.is_empty() {
+            return;
+        }
+        let height = (self.results.len() as u16 + 2).min(area.height);
+        let area = Rect {
+            y: area.y + area.height - height,
+            height,
+            ..area
+        };
+        let library = self.library.as_ref().unwrap().borrow();
+        let items = self
+            .results
+            .iter()
+            .enumerate()
+            .filter_map(|(i, id)| {
+                let SourceItem::Song(_, s) = library.find_by_id(id)? else {
+                    return None;
+                };
+                let item = ListItem::new(format!(
+                    "{} - {}",
+                    s.get_artist().unwrap_or("NO ARTIST"),
+                    s.get_title().unwrap_or("NO TITLE")
+                ));
+                Some(if i == self.selected {
+                    item.fg(Color::Blue)
+                } else {
+                    item
+                })
+            })
+            .collect::<Vec<_>>();
+        frame.render_widget(Clear, area);
+        frame.render_widget(
+            List::new(items).block(
+                Block::new()
+                    .borders(Borders::all())
+                    .border_type(BorderType::Rounded)
+                    .title("Find")
+                    .title_alignment(Alignment::Center),
+            ),
+            area,
+        );
+    }
+}
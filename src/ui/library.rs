@@ -1,24 +1,140 @@
-use crate::song::{Source, SourceItem};
-use crate::ui::{ScrollStatus, UiComponent, UiEvent, UiEventResult};
+use crate::chapters::Chapter;
+use crate::config::{LIBRARY_COLUMNS, SMART_PLAYLISTS};
+use crate::query::{Field, Query};
+use crate::resume::ResumePositions;
+use crate::session::Session;
+use crate::settings::Settings;
+use crate::song::{Song, Source, SourceItem};
+use crate::stats::Stats;
+use crate::ui::{Column, ScrollStatus, UiComponent, UiEvent, UiEventResult};
 use ratatui::prelude::*;
-use ratatui::widgets::Row;
+use ratatui::widgets::{Cell, Row};
 use std::borrow::Cow;
-use std::cell::RefCell;
+use std::cell::{Ref, RefCell};
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::rc::Rc;
+use std::time::Duration;
 use uuid::Uuid;
 
+/// A row of the library table: a source item, a chapter of an expanded song, or an artist or
+/// album of the tree view.
+enum LibraryRow<'a> {
+    Item(SourceItem<'a>),
+    Chapter(&'a Uuid, &'a Chapter),
+    Artist(String),
+    Album(TreeAlbum<'a>),
+}
+
+/// An album of the tree view, with its songs sorted by disc and track number.
+struct TreeAlbum<'a> {
+    artist: String,
+    title: String,
+    year: Option<i32>,
+    songs: Vec<(&'a Uuid, &'a Song)>,
+}
+
+/// An album of the tree view as it is cached, with its songs as positions in the library's
+/// sources and in their items.
+struct AlbumGroup {
+    artist: String,
+    title: String,
+    year: Option<i32>,
+    songs: Vec<(usize, usize)>,
+}
+
+/// A smart playlist shown as a source, listing songs of the other sources.
+struct SmartSource {
+    id: Uuid,
+    title: String,
+    songs: Vec<Uuid>,
+}
+
 pub struct Library {
     items: Vec<(Uuid, Source)>,
+    smart: Vec<SmartSource>,
+    /// Whether the library changed since the smart playlists were evaluated.
+    smart_stale: bool,
+    /// `Stats::changes` when the smart playlists were evaluated, as they may depend on plays,
+    /// ratings and such.
+    smart_stats_changes: u64,
+    /// What random smart playlists are shuffled by, drawn again when sources are added.
+    smart_seed: u64,
+    /// How many times the songs changed, for what depends on them to notice.
+    changes: u64,
+    /// Whether songs are grouped by artist and album instead of by source.
+    tree: bool,
+    /// The albums of the tree view, grouped and sorted once until the songs, or which of them
+    /// are shown, change.
+    albums: RefCell<Option<Vec<AlbumGroup>>>,
+    /// Songs whose chapters are shown.
+    expanded: HashSet<Uuid>,
+    expanded_artists: HashSet<String>,
+    /// Expanded albums of the tree view, as `(artist, album)`.
+    expanded_albums: HashSet<(String, String)>,
+    /// Shown columns with their widths.
+    columns: Vec<(Column, u16)>,
+    /// The column that is moved, resized or sorted by.
+    column: usize,
+    /// The column the songs are sorted by, and whether in descending order.
+    sort: Option<(Column, bool)>,
+    resume: Option<Rc<RefCell<ResumePositions>>>,
+    stats: Option<Rc<RefCell<Stats>>>,
     focused: bool,
-    find: Option<String>,
+    find: Option<Query>,
+    /// Whether songs not matching `find` are hidden.
+    filter: bool,
+    /// The query being typed and where the cursor was when the search started.
+    preview: Option<(String, usize)>,
     viewpoint: RefCell<ScrollStatus>,
 }
 
+/// Whether a row matches a search query. Rows other than songs match by what they show, e.g.
+/// an album row by its title, artist and year.
+fn row_matches(query: &Query, row: &LibraryRow, stats: &Stats) -> bool {
+    match row {
+        LibraryRow::Item(SourceItem::Song(_, s)) => query.matches_song(s, stats),
+        LibraryRow::Item(SourceItem::Title(_, s)) => {
+            query.matches_some(|x| (x == Field::Title).then_some(Cow::Borrowed(s.as_str())))
+        }
+        LibraryRow::Chapter(_, c) => {
+            query.matches_some(|x| (x == Field::Title).then_some(Cow::Borrowed(c.title.as_str())))
+        }
+        LibraryRow::Artist(s) => query.matches_some(|x| {
+            matches!(x, Field::Artist | Field::AlbumArtist).then_some(Cow::Borrowed(s.as_str()))
+        }),
+        LibraryRow::Album(album) => query.matches_some(|x| match x {
+            Field::Album => Some(Cow::Borrowed(album.title.as_str())),
+            Field::Artist | Field::AlbumArtist => Some(Cow::Borrowed(album.artist.as_str())),
+            Field::Year => album.year.map(|x| Cow::Owned(x.to_string())),
+            _ => None,
+        }),
+    }
+}
+
 impl Library {
     pub fn new() -> Self {
         Self {
             items: Vec::new(),
+            smart: Vec::new(),
+            smart_stale: true,
+            smart_stats_changes: 0,
+            // Version 4 uuids are random
+            smart_seed: Uuid::new_v4().as_u64_pair().0,
+            changes: 0,
+            tree: false,
+            albums: RefCell::new(None),
+            expanded: HashSet::new(),
+            expanded_artists: HashSet::new(),
+            expanded_albums: HashSet::new(),
+            columns: LIBRARY_COLUMNS.to_vec(),
+            column: 0,
+            sort: None,
+            resume: None,
+            stats: None,
             focused: false,
             find: None,
+            filter: false,
+            preview: None,
             viewpoint: RefCell::new(ScrollStatus {
                 steps: 0,
                 selected: 0,
@@ -26,10 +142,116 @@ impl Library {
             }),
         }
     }
+    pub fn set_ref_to_resume(&mut self, resume: Rc<RefCell<ResumePositions>>) {
+        self.resume = Some(resume);
+    }
+    pub fn set_ref_to_stats(&mut self, stats: Rc<RefCell<Stats>>) {
+        self.stats = Some(stats);
+    }
+    pub fn stats(&self) -> Ref<'_, Stats> {
+        self.stats.as_ref().unwrap().borrow()
+    }
+    pub fn changes(&self) -> u64 {
+        self.changes
+    }
     pub fn add_source(&mut self, source: Source) {
         self.items.push((Uuid::new_v4(), source));
+        self.regroup();
+        self.smart_stale = true;
+        self.changes += 1;
+        self.smart_seed = Uuid::new_v4().as_u64_pair().0;
+    }
+    /// Adds a playlist source, replacing the one read from the same file if there is one. `ids`
+    /// are the songs it was saved from, see `Source::keep_ids`.
+    pub fn replace_source(&mut self, mut source: Source, ids: &[Uuid]) {
+        match self.items.iter_mut().find(|(_, x)| {
+            x.playlist_path().is_some() && x.playlist_path() == source.playlist_path()
+        }) {
+            Some((_, x)) => {
+                source.keep_ids(x, ids);
+                *x = source;
+                self.regroup();
+                self.smart_stale = true;
+                self.changes += 1;
+                self.smart_seed = Uuid::new_v4().as_u64_pair().0;
+            }
+            None => self.add_source(source),
+        }
+    }
+    /// Reads the metadata of the songs of these files again, e.g. after their tags were changed.
+    pub fn forget_metadata(&mut self, paths: &[String]) {
+        for (_, source) in self.items.iter_mut() {
+            for path in paths {
+                source.forget_metadata(path);
+            }
+        }
+        self.regroup();
+        self.smart_stale = true;
+        self.changes += 1;
     }
-    pub fn find_by_id(&self, id: &Uuid) -> Option<SourceItem> {
+    /// Points the songs of a file to its new path after it was renamed.
+    pub fn rename(&mut self, from: &str, to: &str) {
+        for (_, source) in self.items.iter_mut() {
+            source.rename(from, to);
+        }
+        self.regroup();
+        self.smart_stale = true;
+        self.changes += 1;
+    }
+    /// Re-evaluates the smart playlists.
+    fn refresh_smart(&mut self) {
+        let smart = SMART_PLAYLISTS
+            .iter()
+            .map(|x| (x.title.to_owned(), x.evaluate(self, self.smart_seed)))
+            .collect::<Vec<_>>();
+        // Title ids are kept, so that e.g. the cursor doesn't have to move
+        let ids = self
+            .smart
+            .iter()
+            .map(|x| x.id)
+            .chain(std::iter::repeat_with(Uuid::new_v4));
+        self.smart = smart
+            .into_iter()
+            .zip(ids)
+            .map(|((title, songs), id)| SmartSource { id, title, songs })
+            .collect();
+        self.smart_stale = false;
+    }
+    /// Returns the items of every source, each beginning with its title. Smart playlists come
+    /// last.
+    fn sources(&self) -> Vec<Vec<SourceItem<'_>>> {
+        let mut sources = self
+            .items
+            .iter()
+            .map(|(x, y)| y.iter(x).collect::<Vec<_>>())
+            .collect::<Vec<_>>();
+        if !self.smart.is_empty() {
+            let songs = self.songs().collect::<HashMap<_, _>>();
+            sources.extend(self.smart.iter().map(|x| {
+                std::iter::once(SourceItem::Title(&x.id, &x.title))
+                    .chain(x.songs.iter().filter_map(|y| {
+                        songs.get_key_value(y).map(|(y, s)| SourceItem::Song(y, s))
+                    }))
+                    .collect()
+            }));
+        }
+        sources
+    }
+    /// Returns the songs of the source whose title has the given id, leaving out the ones hidden
+    /// by the filter.
+    pub fn songs_of(&self, title_id: &Uuid) -> Vec<Uuid> {
+        self.sources()
+            .into_iter()
+            .filter(|x| matches!(x.first(), Some(SourceItem::Title(y, _)) if *y == title_id))
+            .flatten()
+            .filter_map(|x| match x {
+                SourceItem::Song(y, s) if self.shown(s) => Some(*y),
+                SourceItem::Song(..) => None,
+                SourceItem::Title(..) => None,
+            })
+            .collect()
+    }
+    pub fn find_by_id(&self, id: &Uuid) -> Option<SourceItem<'_>> {
         self.items
             .iter()
             .flat_map(|(x, y)| y.iter(x))
@@ -38,19 +260,551 @@ impl Library {
                 SourceItem::Song(y, _) => *id == **y,
             })
     }
-    pub fn selected_id(&self) -> Uuid {
+    /// Iterates over the songs of all sources. Songs found in more than one source are only
+    /// listed once.
+    pub fn songs(&self) -> impl Iterator<Item = (&Uuid, &Song)> {
+        let mut seen = HashSet::new();
         self.items
             .iter()
             .flat_map(|(x, y)| y.iter(x))
+            .filter_map(move |x| match x {
+                SourceItem::Song(id, s) if seen.insert(s.key()) => Some((id, s)),
+                _ => None,
+            })
+    }
+    /// Forgets the albums of the tree view, to group the songs again.
+    fn regroup(&mut self) {
+        *self.albums.get_mut() = None;
+    }
+    /// The albums of the tree view, grouping the songs if they changed.
+    fn albums(&self) -> Vec<TreeAlbum<'_>> {
+        if self.albums.borrow().is_none() {
+            let albums = self.group_albums();
+            *self.albums.borrow_mut() = Some(albums);
+        }
+        self.albums
+            .borrow()
+            .iter()
+            .flatten()
+            .map(|x| TreeAlbum {
+                artist: x.artist.clone(),
+                title: x.title.clone(),
+                year: x.year,
+                songs: x
+                    .songs
+                    .iter()
+                    .filter_map(|(i, j)| self.items.get(*i)?.1.song(*j))
+                    .collect(),
+            })
+            .collect()
+    }
+    /// Groups the shown songs into albums, sorted by album artist (or artist), year and title.
+    fn group_albums(&self) -> Vec<AlbumGroup> {
+        let mut seen = HashSet::new();
+        let songs = self.items.iter().enumerate().flat_map(|(i, (x, y))| {
+            y.iter(x)
+                .skip(1)
+                .enumerate()
+                .filter_map(move |(j, x)| match x {
+                    SourceItem::Song(_, s) => Some(((i, j), s)),
+                    SourceItem::Title(..) => None,
+                })
+        });
+        let mut albums = BTreeMap::<(String, i32, String), (AlbumGroup, Vec<_>)>::new();
+        for (position, s) in songs.filter(|(_, s)| seen.insert(s.key()) && self.shown(s)) {
+            let artist = match (s.get_album_artist(), s.get_artist()) {
+                (Ok(x), _) if !x.is_empty() => x,
+                (_, Ok(x)) if !x.is_empty() => x,
+                _ => "Unknown Artist",
+            };
+            let title = match s.get_album() {
+                Ok(x) if !x.is_empty() => x,
+                _ => "Unknown Album",
+            };
+            let year = s.get_year().unwrap_or_default();
+            albums
+                .entry((
+                    artist.to_lowercase(),
+                    year.unwrap_or(i32::MAX),
+                    title.to_lowercase(),
+                ))
+                .or_insert_with(|| {
+                    let album = AlbumGroup {
+                        artist: artist.to_owned(),
+                        title: title.to_owned(),
+                        year,
+                        songs: Vec::new(),
+                    };
+                    (album, Vec::new())
+                })
+                .1
+                .push((position, s));
+        }
+        albums
+            .into_values()
+            .map(|(mut album, mut songs)| {
+                songs.sort_by_key(|(_, s)| {
+                    (
+                        s.get_disc().unwrap_or_default().unwrap_or(0),
+                        s.get_track().unwrap_or_default().unwrap_or(u16::MAX),
+                    )
+                });
+                album.songs = songs.into_iter().map(|(x, _)| x).collect();
+                album
+            })
+            .collect()
+    }
+    /// Whether non-matching songs are hidden, which needs a search.
+    fn filtering(&self) -> bool {
+        self.filter && self.find.is_some()
+    }
+    /// Whether the song is shown, i.e. not hidden by the filter.
+    fn shown(&self, song: &Song) -> bool {
+        match (&self.find, self.filter) {
+            (Some(query), true) => query.matches_song(song, &self.stats()),
+            _ => true,
+        }
+    }
+    /// Describes the search for the status line: how many songs match, and whether the others
+    /// are hidden.
+    pub fn find_summary(&self) -> String {
+        let Some(ref query) = self.find else {
+            return "Nothing searched".to_owned();
+        };
+        let stats = self.stats();
+        let count = self
+            .songs()
+            .filter(|(_, s)| query.matches_song(s, &stats))
+            .count();
+        let summary = if self.filter {
+            format!("{count} matches, others hidden")
+        } else {
+            format!("{count} matches")
+        };
+        match query.invalid() {
+            [] => summary,
+            x => format!("{summary} (ignored {}, not numbers)", x.join(" ")),
+        }
+    }
+    /// Hides or shows again the songs not matching the search, keeping the selected song
+    /// selected if it is still shown.
+    pub fn toggle_filter(&mut self) {
+        let rows = self.rows();
+        let selected = self.viewpoint.borrow().selected;
+        let song = match rows.get(selected) {
+            Some(LibraryRow::Item(SourceItem::Song(id, _))) => Some(**id),
+            _ => None,
+        };
+        drop(rows);
+        self.filter = !self.filter;
+        self.regroup();
+        let position = song.and_then(|id| {
+            self.rows()
+                .iter()
+                .position(|x| matches!(x, LibraryRow::Item(SourceItem::Song(y, _)) if **y == id))
+        });
+        self.viewpoint.borrow_mut().steps = position.unwrap_or(0) as isize - selected as isize;
+    }
+    fn shows(&self, column: Column) -> bool {
+        self.columns.iter().any(|(x, _)| *x == column)
+    }
+    /// Sorts songs by the column chosen to sort by, keeping their order if there is none.
+    fn sort_songs<T>(&self, items: &mut [T], song: impl Fn(&T) -> &Song) {
+        if let Some((column, descending)) = self.sort {
+            let stats = self.stats();
+            items.sort_by(|x, y| {
+                let order = column.compare(song(x), song(y), &stats);
+                if descending {
+                    order.reverse()
+                } else {
+                    order
+                }
+            });
+        }
+    }
+    /// Moves the column cursor by `steps`, or with `swap` the column under it.
+    fn move_column(&mut self, steps: isize, swap: bool) {
+        let to = self
+            .column
+            .saturating_add_signed(steps)
+            .min(self.columns.len() - 1);
+        if swap {
+            self.columns.swap(self.column, to);
+        }
+        self.column = to;
+    }
+    fn resize_column(&mut self, delta: i16) {
+        let (_, width) = &mut self.columns[self.column];
+        *width = width.saturating_add_signed(delta).max(1);
+    }
+    /// Shows the first hidden column, right after the column cursor.
+    fn add_column(&mut self) {
+        if let Some(x) = Column::ALL.into_iter().find(|x| !self.shows(*x)) {
+            self.columns
+                .insert(self.column + 1, (x, x.header().chars().count() as u16 + 2));
+            self.column += 1;
+        }
+    }
+    /// Hides the column under the cursor. The `Title` column can't be hidden.
+    fn remove_column(&mut self) {
+        if self.columns[self.column].0 != Column::Title {
+            let (x, _) = self.columns.remove(self.column);
+            if self.sort.is_some_and(|(y, _)| x == y) {
+                self.sort = None;
+            }
+            self.column = self.column.min(self.columns.len() - 1);
+        }
+    }
+    /// Sorts by the column under the cursor, ascending, then descending, then not at all.
+    fn cycle_sort(&mut self) {
+        let column = self.columns[self.column].0;
+        self.sort = match self.sort {
+            _ if column == Column::Index => None,
+            Some((x, false)) if x == column => Some((column, true)),
+            Some((x, true)) if x == column => None,
+            _ => Some((column, false)),
+        };
+    }
+    fn cell<'a>(&'a self, column: Column, i: usize, row: &LibraryRow<'a>) -> Cow<'a, str> {
+        let time =
+            |x: Duration| Cow::Owned(format!("{:02}:{:02}", x.as_secs() / 60, x.as_secs() % 60));
+        match (column, row) {
+            (Column::Index, LibraryRow::Item(SourceItem::Title(..))) => Cow::Borrowed("====="),
+            (Column::Index, LibraryRow::Item(SourceItem::Song(id, s))) => Cow::Owned(format!(
+                "{:4}{}",
+                i,
+                match s.get_chapters().map(|x| x.is_empty()) {
+                    Ok(false) if self.expanded.contains(id) => '-',
+                    Ok(false) => '+',
+                    _ => ' ',
+                }
+            )),
+            (Column::Title, LibraryRow::Item(SourceItem::Title(_, s))) => Cow::Borrowed(s.as_str()),
+            (Column::Title, LibraryRow::Item(SourceItem::Song(_, s))) => Cow::Owned(format!(
+                "{}{}{}",
+                match (
+                    self.resume.as_ref().unwrap().borrow().get(&s.key()),
+                    s.get_duration().map(|x| x.as_millis() as u64 / 100),
+                ) {
+                    (Some(p), Ok(d)) if d > 0 => Cow::Owned(format!("[{:2}%] ", p * 100 / d)),
+                    _ => Cow::Borrowed(""),
+                },
+                if self.shows(Column::Artist) {
+                    Cow::Borrowed("")
+                } else {
+                    Cow::Owned(format!("{} - ", s.get_artist().unwrap_or("NO ARTIST")))
+                },
+                s.get_title().unwrap_or("NO TITLE")
+            )),
+            (Column::Title, LibraryRow::Chapter(_, c)) => Cow::Owned(format!("  - {}", c.title)),
+            (Column::Title, LibraryRow::Artist(s)) => Cow::Owned(format!(
+                "{} {s}",
+                if self.expanded_artists.contains(s) {
+                    '-'
+                } else {
+                    '+'
+                }
+            )),
+            (Column::Title, LibraryRow::Album(x)) => Cow::Owned(format!(
+                "  {} {}{}",
+                if self
+                    .expanded_albums
+                    .contains(&(x.artist.clone(), x.title.clone()))
+                {
+                    '-'
+                } else {
+                    '+'
+                },
+                x.title,
+                match x.year {
+                    Some(y) => format!(" ({y})"),
+                    None => String::new(),
+                }
+            )),
+            (Column::Duration, LibraryRow::Chapter(_, c)) => time(c.start),
+            (Column::Duration, LibraryRow::Album(x)) => time(
+                x.songs
+                    .iter()
+                    .filter_map(|(_, s)| s.get_duration().ok())
+                    .sum(),
+            ),
+            (_, LibraryRow::Item(SourceItem::Song(_, s))) => column.text(s, &self.stats()),
+            _ => Cow::Borrowed(""),
+        }
+    }
+    /// Adds a source item to `rows`, followed by its chapters if it is an expanded song.
+    fn push_item<'a>(&'a self, rows: &mut Vec<LibraryRow<'a>>, x: SourceItem<'a>) {
+        let chapters = match x {
+            SourceItem::Song(id, s) if self.expanded.contains(id) => Some((id, s)),
+            _ => None,
+        };
+        rows.push(LibraryRow::Item(x));
+        if let Some((id, s)) = chapters {
+            rows.extend(
+                s.get_chapters()
+                    .unwrap_or_default()
+                    .iter()
+                    .map(|y| LibraryRow::Chapter(id, y)),
+            );
+        }
+    }
+    fn rows(&self) -> Vec<LibraryRow<'_>> {
+        let mut rows = Vec::new();
+        if !self.tree {
+            for items in self.sources() {
+                let mut items = items
+                    .into_iter()
+                    .filter(|x| match x {
+                        SourceItem::Song(_, s) => self.shown(s),
+                        SourceItem::Title(..) => true,
+                    })
+                    .collect::<Vec<_>>();
+                if items.len() == 1 && self.filtering() {
+                    // No song of the source matches
+                    continue;
+                }
+                // The source title stays first
+                self.sort_songs(&mut items[1..], |x| match x {
+                    SourceItem::Song(_, s) => s,
+                    SourceItem::Title(..) => unreachable!(),
+                });
+                for x in items {
+                    self.push_item(&mut rows, x);
+                }
+            }
+            return rows;
+        }
+        let mut artist = None;
+        for album in self.albums() {
+            if artist.as_ref() != Some(&album.artist) {
+                artist = Some(album.artist.clone());
+                rows.push(LibraryRow::Artist(album.artist.clone()));
+            }
+            // Matches are shown without having to expand their artists and albums
+            if !self.expanded_artists.contains(&album.artist) && !self.filtering() {
+                continue;
+            }
+            let expanded = self.filtering()
+                || self
+                    .expanded_albums
+                    .contains(&(album.artist.clone(), album.title.clone()));
+            let mut songs = album.songs.clone();
+            self.sort_songs(&mut songs, |(_, s)| s);
+            rows.push(LibraryRow::Album(album));
+            if expanded {
+                for (id, s) in songs {
+                    self.push_item(&mut rows, SourceItem::Song(id, s));
+                }
+            }
+        }
+        rows
+    }
+    /// Returns the songs of the selected row: the song itself, the song whose chapter is
+    /// selected, or every song of the selected source, artist or album.
+    pub fn selected_songs(&self) -> Vec<Uuid> {
+        match self
+            .rows()
+            .into_iter()
+            .nth(self.viewpoint.borrow().selected)
+        {
+            Some(LibraryRow::Item(SourceItem::Title(id, _))) => self.songs_of(id),
+            Some(LibraryRow::Item(SourceItem::Song(id, _))) => vec![*id],
+            Some(LibraryRow::Chapter(id, _)) => vec![*id],
+            Some(LibraryRow::Artist(artist)) => self
+                .albums()
+                .into_iter()
+                .filter(|x| x.artist == artist)
+                .flat_map(|x| x.songs)
+                .map(|(x, _)| *x)
+                .collect(),
+            Some(LibraryRow::Album(album)) => album.songs.into_iter().map(|(x, _)| *x).collect(),
+            None => Vec::new(),
+        }
+    }
+    /// Returns the whole files among the selected songs, once each. CUE tracks are left out, as
+    /// they share their file and get their tags from the sheet.
+    pub fn selected_files(&self) -> Vec<Song> {
+        let mut paths = HashSet::new();
+        self.selected_songs()
+            .iter()
+            .filter_map(|x| match self.find_by_id(x)? {
+                SourceItem::Song(_, s @ Song::File { .. }) => Some(s.clone()),
+                _ => None,
+            })
+            .filter(|x| paths.insert(x.path().to_owned()))
+            .collect()
+    }
+    /// Returns the selected song, or the song of the selected chapter.
+    pub fn selected_song(&self) -> Option<Song> {
+        match self
+            .rows()
+            .into_iter()
+            .nth(self.viewpoint.borrow().selected)
+        {
+            Some(LibraryRow::Item(SourceItem::Song(_, s))) => Some(s.clone()),
+            Some(LibraryRow::Chapter(id, _)) => match self.find_by_id(id) {
+                Some(SourceItem::Song(_, s)) => Some(s.clone()),
+                _ => None,
+            },
+            _ => None,
+        }
+    }
+    /// Returns the start of the selected chapter, if a chapter is selected.
+    pub fn selected_chapter_start(&self) -> Option<Duration> {
+        match self
+            .rows()
+            .into_iter()
             .nth(self.viewpoint.borrow().selected)
-            .map(|x| match x {
-                SourceItem::Title(y, _) => *y,
-                SourceItem::Song(y, _) => *y,
+        {
+            Some(LibraryRow::Chapter(_, x)) => Some(x.start),
+            _ => None,
+        }
+    }
+    /// Switches between listing songs by source and grouping them by artist and album.
+    fn toggle_tree(&mut self) {
+        self.tree = !self.tree;
+        *self.viewpoint.borrow_mut() = ScrollStatus {
+            steps: 0,
+            selected: 0,
+            offset: 0,
+        };
+    }
+    /// Expands or collapses the selected artist, album or song (showing its chapters). On a
+    /// chapter, its song is collapsed and selected.
+    fn toggle_expanded(&mut self) {
+        enum Toggle {
+            Song(Uuid),
+            Chapter(Uuid),
+            Artist(String),
+            Album(String, String),
+        }
+        let selected = self.viewpoint.borrow().selected;
+        let toggle = match self.rows().into_iter().nth(selected) {
+            Some(LibraryRow::Item(SourceItem::Song(id, s)))
+                if !s.get_chapters().unwrap_or_default().is_empty() =>
+            {
+                Toggle::Song(*id)
+            }
+            Some(LibraryRow::Chapter(id, _)) => Toggle::Chapter(*id),
+            Some(LibraryRow::Artist(x)) => Toggle::Artist(x),
+            Some(LibraryRow::Album(x)) => Toggle::Album(x.artist, x.title),
+            _ => return,
+        };
+        match toggle {
+            Toggle::Song(id) => {
+                if !self.expanded.remove(&id) {
+                    self.expanded.insert(id);
+                }
+            }
+            Toggle::Chapter(id) => {
+                let song = self
+                    .rows()
+                    .into_iter()
+                    .position(|x| matches!(x, LibraryRow::Item(SourceItem::Song(y, _)) if *y == id))
+                    .unwrap();
+                self.expanded.remove(&id);
+                self.viewpoint.borrow_mut().steps -= (selected - song) as isize;
+            }
+            Toggle::Artist(x) => {
+                if !self.expanded_artists.remove(&x) {
+                    self.expanded_artists.insert(x);
+                }
+            }
+            Toggle::Album(x, y) => {
+                if !self.expanded_albums.remove(&(x.clone(), y.clone())) {
+                    self.expanded_albums.insert((x, y));
+                }
+            }
+        }
+    }
+    pub fn find_by_key(&self, key: &str) -> Option<Uuid> {
+        self.items
+            .iter()
+            .flat_map(|(x, y)| y.iter(x))
+            .find_map(|x| match x {
+                SourceItem::Song(y, s) if s.key() == key => Some(*y),
+                _ => None,
             })
-            .unwrap()
     }
-    pub fn set_find(&mut self, find: Option<String>) {
+    pub fn find_song(&self, predicate: impl Fn(&Song) -> bool) -> Option<&Song> {
+        self.items
+            .iter()
+            .flat_map(|(x, y)| y.iter(x))
+            .find_map(|x| match x {
+                SourceItem::Song(_, s) if predicate(s) => Some(s),
+                _ => None,
+            })
+    }
+    pub fn save_settings(&self, settings: &mut Settings) {
+        settings.library_columns = if self.columns == LIBRARY_COLUMNS {
+            Vec::new()
+        } else {
+            self.columns.clone()
+        };
+        settings.library_sort = self.sort;
+    }
+    pub fn restore_settings(&mut self, settings: &Settings) {
+        if !settings.library_columns.is_empty() {
+            self.columns = settings.library_columns.clone();
+        }
+        self.sort = settings.library_sort;
+    }
+    pub fn save_session(&self, session: &mut Session) {
+        session.library_tree = self.tree;
+        session.library_selected = self.viewpoint.borrow().selected;
+    }
+    pub fn restore_session(&mut self, session: &Session) {
+        self.tree = session.library_tree;
+        self.viewpoint.borrow_mut().steps = session.library_selected as isize;
+    }
+    pub fn set_find(&mut self, find: Option<Query>) {
         self.find = find;
+        self.preview = None;
+        self.regroup();
+    }
+    /// Clears the search, moving the cursor back to where it was if the search was being typed.
+    pub fn cancel_find(&mut self) {
+        if let Some((_, origin)) = self.preview {
+            self.select(origin);
+        }
+        self.set_find(None);
+    }
+    /// Highlights the matches of a query while it is being typed, moving the cursor to the
+    /// first match from where it was when the search started.
+    pub fn preview_find(&mut self, text: &str) {
+        let origin = match self.preview {
+            Some((ref x, _)) if x == text => return,
+            Some((_, x)) => x,
+            None => self.viewpoint.borrow().selected,
+        };
+        self.find = Some(Query::parse(text)).filter(|x| !x.is_empty());
+        self.preview = Some((text.to_owned(), origin));
+        self.regroup();
+        let row = self.find_match(origin as isize, true);
+        self.select(row.unwrap_or(origin));
+    }
+    /// Returns the first row matching the search, starting at `from` and going forward or
+    /// backward, wrapping around at the ends.
+    fn find_match(&self, from: isize, forward: bool) -> Option<usize> {
+        let query = self.find.as_ref()?;
+        let rows = self.rows();
+        let len = rows.len() as isize;
+        let step = if forward { 1 } else { -1 };
+        (0..len)
+            .map(|i| from + i * step)
+            .map(|x| x.rem_euclid(len) as usize)
+            .find(|x| row_matches(query, &rows[*x], &self.stats()))
+    }
+    /// Moves the cursor to the next (or previous) match, wrapping around.
+    fn jump_to_match(&mut self, forward: bool) {
+        let selected = self.viewpoint.borrow().selected as isize;
+        if let Some(x) = self.find_match(selected + if forward { 1 } else { -1 }, forward) {
+            self.select(x);
+        }
+    }
+    fn select(&mut self, row: usize) {
+        let mut viewpoint = self.viewpoint.borrow_mut();
+        viewpoint.steps = row as isize - viewpoint.selected as isize;
     }
     fn next(&mut self) {
         self.viewpoint.borrow_mut().steps += 1;
@@ -73,6 +827,58 @@ impl UiComponent for Library {
                     self.prev();
                     UiEventResult::Handled
                 }
+                C::Char('n') => {
+                    self.jump_to_match(true);
+                    UiEventResult::Handled
+                }
+                C::Char('N') => {
+                    self.jump_to_match(false);
+                    UiEventResult::Handled
+                }
+                C::Char('o') => {
+                    self.toggle_expanded();
+                    UiEventResult::Handled
+                }
+                C::Char('t') => {
+                    self.toggle_tree();
+                    UiEventResult::Handled
+                }
+                C::Char('h') => {
+                    self.move_column(-1, false);
+                    UiEventResult::Handled
+                }
+                C::Char('l') => {
+                    self.move_column(1, false);
+                    UiEventResult::Handled
+                }
+                C::Char('H') => {
+                    self.move_column(-1, true);
+                    UiEventResult::Handled
+                }
+                C::Char('L') => {
+                    self.move_column(1, true);
+                    UiEventResult::Handled
+                }
+                C::Char('<') => {
+                    self.resize_column(-1);
+                    UiEventResult::Handled
+                }
+                C::Char('>') => {
+                    self.resize_column(1);
+                    UiEventResult::Handled
+                }
+                C::Char('a') => {
+                    self.add_column();
+                    UiEventResult::Handled
+                }
+                C::Char('x') => {
+                    self.remove_column();
+                    UiEventResult::Handled
+                }
+                C::Char('s') => {
+                    self.cycle_sort();
+                    UiEventResult::Handled
+                }
                 _ => UiEventResult::PassThrough,
             },
             UiEvent::FocusGained => {
@@ -83,7 +889,19 @@ impl UiComponent for Library {
                 self.focused = false;
                 UiEventResult::Handled
             }
-            UiEvent::Tick => UiEventResult::PassThrough,
+            UiEvent::Tick => {
+                let changes = self.stats().changes();
+                if changes != self.smart_stats_changes {
+                    self.smart_stats_changes = changes;
+                    self.smart_stale = true;
+                    // The filter may depend on them too
+                    self.regroup();
+                }
+                if self.smart_stale {
+                    self.refresh_smart();
+                }
+                UiEventResult::PassThrough
+            }
         }
     }
     fn render(&self, frame: &mut Frame, area: Rect) {
@@ -101,75 +919,64 @@ impl UiComponent for Library {
         let width = block.inner(area).width;
         let height = (block.inner(area).height - 1) as usize;
 
-        let len = self.items.iter().flat_map(|(x, y)| y.iter(x)).count();
-        let selected = self.viewpoint.borrow().selected;
-        let steps = self.viewpoint.borrow().steps;
-        if let Some(ref str) = self.find {
-            if steps > 0 {
-                if let Some(z) = self
-                    .items
-                    .iter()
-                    .flat_map(|(x, y)| y.iter(x))
-                    .skip(selected)
-                    .enumerate()
-                    .skip(1)
-                    .find_map(|(i, x)| {
-                        match x {
-                            SourceItem::Title(_, s) => s.contains(str),
-                            SourceItem::Song(_, s) => s.get_title().unwrap_or("").contains(str),
-                        }
-                        .then_some(i)
-                    })
-                {
-                    self.viewpoint.borrow_mut().steps = z as isize;
-                }
-            }
-        }
+        let rows = self.rows();
+        let len = rows.len();
         self.viewpoint.borrow_mut().calculate_steps(len, height);
 
+        let spacing = self.columns.len().saturating_sub(1) as u16;
+        let others = self
+            .columns
+            .iter()
+            .filter(|(x, _)| *x != Column::Title)
+            .map(|(_, x)| x)
+            .sum::<u16>();
+        let widths = self
+            .columns
+            .iter()
+            .map(|(x, w)| match x {
+                Column::Title => C::Length(width.saturating_sub(others + spacing).max(*w)),
+                _ => C::Length(*w),
+            })
+            .collect::<Vec<_>>();
+        let header = self
+            .columns
+            .iter()
+            .enumerate()
+            .map(|(i, (x, _))| {
+                let title = match x {
+                    Column::Title if !self.shows(Column::Artist) => "Artist - Title",
+                    _ => x.header(),
+                };
+                let title = match self.sort {
+                    Some((y, false)) if y == *x => format!("{title}↑"),
+                    Some((y, true)) if y == *x => format!("{title}↓"),
+                    _ => title.to_owned(),
+                };
+                if self.focused && i == self.column {
+                    Cell::from(title).reversed()
+                } else {
+                    Cell::from(title)
+                }
+            })
+            .collect::<Vec<_>>();
+
         let table = ratatui::widgets::Table::new(
-            self.items
-                .iter()
-                .flat_map(|(x, y)| y.iter(x))
+            rows.into_iter()
                 .enumerate()
                 .skip(self.viewpoint.borrow().selected - self.viewpoint.borrow().offset)
                 .take(height)
                 .map(|(i, x)| {
-                    match x {
-                        SourceItem::Title(_, s) => Row::new(["=====", s, ""]).underlined(),
-                        SourceItem::Song(_, s) => Row::new([
-                            Cow::Owned(format!("{:5}", i)),
-                            Cow::Owned(format!(
-                                "{} - {}",
-                                s.get_artist().unwrap_or("NO ARTIST"),
-                                s.get_title().unwrap_or("NO TITLE")
-                            )),
-                            {
-                                let duration = match s.get_duration() {
-                                    Ok(x) => Some(x.as_secs()),
-                                    Err(_) => None,
-                                };
-                                match duration {
-                                    Some(x) => Cow::Owned(format!("{:02}:{:02}", x / 60, x % 60)),
-                                    None => Cow::Borrowed("--:--"),
-                                }
-                            },
-                        ]),
+                    let row = Row::new(self.columns.iter().map(|(c, _)| self.cell(*c, i, &x)));
+                    match &x {
+                        LibraryRow::Item(SourceItem::Title(..)) => row.underlined(),
+                        LibraryRow::Artist(_) => row.bold(),
+                        _ => row,
                     }
                     .fg({
-                        let found = if self.find.is_some() {
-                            let str = match x {
-                                SourceItem::Title(_, s) => Cow::Borrowed(s),
-                                SourceItem::Song(_, s) => Cow::Owned(format!(
-                                    "{} {}",
-                                    s.get_title().unwrap_or(""),
-                                    s.get_artist().unwrap_or("")
-                                )),
-                            };
-                            str.contains(self.find.as_ref().unwrap())
-                        } else {
-                            false
-                        };
+                        let found = self
+                            .find
+                            .as_ref()
+                            .is_some_and(|query| row_matches(query, &x, &self.stats()));
                         if found && i == self.viewpoint.borrow().selected {
                             Color::LightMagenta
                         } else if found {
@@ -181,10 +988,10 @@ impl UiComponent for Library {
                         }
                     })
                 }),
-            [C::Length(5), C::Length(width - 15), C::Length(8)],
+            widths,
         )
         .block(block)
-        .header(Row::new(vec!["  #", "Artist - Title", "Duration"]).underlined());
+        .header(Row::new(header).underlined());
         frame.render_widget(table, area);
     }
 }
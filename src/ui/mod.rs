@@ -2,12 +2,26 @@ mod tui;
 pub use tui::Tui;
 mod status_line;
 pub use status_line::StatusLine;
+mod column;
+pub use column::Column;
+mod finder;
+pub use finder::Finder;
 mod library;
 pub use library::Library;
+mod dashboard;
+pub use dashboard::Dashboard;
+mod tag_editor;
+pub use tag_editor::TagEditor;
+mod batch;
+pub use batch::BatchPreview;
 mod playlist;
 pub use playlist::Playlist;
 mod player;
 pub use player::Player;
+mod cover;
+pub use cover::Cover;
+mod visualizer;
+pub use visualizer::Visualizer;
 
 use ratatui::prelude::*;
 use ratatui::Frame;
@@ -28,12 +42,14 @@ pub trait UiComponent {
     fn render(&self, frame: &mut Frame, area: Rect);
 }
 
-#[derive(Copy, Clone)]
+#[derive(Copy, Clone, Default, serde::Serialize, serde::Deserialize)]
 pub enum Focus {
+    #[default]
     Library,
     Playlist,
     Player,
     StatusLine,
+    Dashboard,
 }
 
 struct ScrollStatus {
@@ -43,6 +59,24 @@ struct ScrollStatus {
 }
 impl ScrollStatus {
     pub fn calculate_steps(&mut self, len: usize, height: usize) {
+        if len == 0 {
+            *self = Self {
+                steps: 0,
+                selected: 0,
+                offset: 0,
+            };
+            return;
+        }
+        // The list may have shrunk since the last time, e.g. when collapsing or filtering it
+        let height = height.max(1);
+        self.selected = self.selected.min(len - 1);
+        let below = (len - 1) - self.selected;
+        let view = std::cmp::min(len, height);
+        self.offset = self
+            .offset
+            .min(view - 1)
+            .min(self.selected)
+            .max((view - 1).saturating_sub(below));
         for _ in self.steps..0 {
             match (self.selected, self.offset) {
                 (0, 0) => {}
@@ -1,16 +1,44 @@
+use crate::config::SHOW_LEVEL_METERS;
+use crate::ui::playlist::SleepTimer;
 use crate::ui::{Playlist, UiComponent, UiEvent, UiEventResult};
 use ratatui::layout::{Alignment, Constraint, Direction, Layout, Rect};
-use ratatui::prelude::{Color, Style};
+use ratatui::prelude::{Color, Line, Span, Style};
+use ratatui::widgets::block::{Position, Title};
 use ratatui::widgets::Paragraph;
 use ratatui::Frame;
 use std::borrow::Cow;
 use std::cell::RefCell;
 use std::rc::Rc;
 use std::sync::atomic::Ordering;
+use std::time::{Duration, Instant};
+
+/// The lowest level shown by the meters, in dBFS.
+const FLOOR_DB: f32 = -48.;
+/// Frames the RMS is measured over, about 90ms at 44.1kHz.
+const RMS_FRAMES: usize = 4096;
+/// How long the highest peak stays marked.
+const PEAK_HOLD: Duration = Duration::from_millis(1500);
+/// How long clipping stays indicated.
+const CLIP_HOLD: Duration = Duration::from_secs(3);
+/// Samples this loud are taken as clipping, as full scale can't quite be reached in integers.
+const CLIP_LEVEL: f32 = 0.999;
+
+/// What a level meter remembers between frames.
+#[derive(Clone, Copy)]
+struct Meter {
+    /// The highest peak lately, and when it was reached.
+    hold: (f32, Instant),
+    clipped: Option<Instant>,
+}
+
+fn db(x: f32) -> f32 {
+    20. * x.max(1e-9).log10()
+}
 
 pub struct Player {
     playlist: Option<Rc<RefCell<Playlist>>>,
     focused: bool,
+    meters: RefCell<[Meter; 2]>,
 }
 
 impl Player {
@@ -18,11 +46,75 @@ impl Player {
         Self {
             playlist: None,
             focused: false,
+            meters: RefCell::new(
+                [Meter {
+                    hold: (0., Instant::now()),
+                    clipped: None,
+                }; 2],
+            ),
         }
     }
     pub fn set_ref_to_playlist(&mut self, playlist: Rc<RefCell<Playlist>>) {
         self.playlist = Some(playlist);
     }
+    /// A peak meter of the left and of the right channel, filled up to the RMS level and marking
+    /// the held peak, followed by that peak in dB, or by `CLIP` if the channel clipped lately.
+    /// Mono is shown on both.
+    fn level_meters(&self, width: u16) -> Vec<Line<'static>> {
+        let playlist = self.playlist.as_ref().unwrap().borrow();
+        let (rms, peaks) = if playlist.playing().is_some() {
+            let mut samples = playlist.samples.lock().unwrap();
+            (samples.rms(RMS_FRAMES), samples.take_peaks())
+        } else {
+            (vec![0.], vec![0.])
+        };
+        let now = Instant::now();
+        let mut meters = self.meters.borrow_mut();
+        let length = width.saturating_sub(7) as usize;
+        ["L", "R"]
+            .into_iter()
+            .enumerate()
+            .map(|(i, label)| {
+                let meter = &mut meters[i];
+                let (rms, peak) = (rms[i.min(rms.len() - 1)], peaks[i.min(peaks.len() - 1)]);
+                if peak >= meter.hold.0 || now - meter.hold.1 > PEAK_HOLD {
+                    meter.hold = (peak, now);
+                }
+                if peak >= CLIP_LEVEL {
+                    meter.clipped = Some(now);
+                }
+                let cell = |x: f32| ((db(x) - FLOOR_DB) / -FLOOR_DB * length as f32).round();
+                let filled = cell(rms).clamp(0., length as f32) as usize;
+                let hold = (cell(meter.hold.0) as usize).checked_sub(1);
+                let mut spans = vec![Span::raw(format!("{label} "))];
+                spans.extend((0..length).map(|x| {
+                    // Green up to -12dB, yellow up to -3dB, red above
+                    let color = match FLOOR_DB - FLOOR_DB * (x + 1) as f32 / length as f32 {
+                        x if x > -3. => Color::LightRed,
+                        x if x > -12. => Color::Yellow,
+                        _ => Color::Green,
+                    };
+                    if x < filled {
+                        Span::styled("█", Style::default().fg(color))
+                    } else if Some(x) == hold {
+                        Span::styled("▏", Style::default().fg(color))
+                    } else {
+                        Span::styled("·", Style::default().fg(Color::DarkGray))
+                    }
+                }));
+                spans.push(match meter.clipped {
+                    Some(x) if now - x < CLIP_HOLD => {
+                        Span::styled(" CLIP", Style::default().fg(Color::White).bg(Color::Red))
+                    }
+                    _ if meter.hold.0 > 0. && db(meter.hold.0) >= FLOOR_DB => {
+                        Span::raw(format!("{:5.0}", db(meter.hold.0)))
+                    }
+                    _ => Span::raw("   -∞"),
+                });
+                Line::from(spans)
+            })
+            .collect()
+    }
 }
 
 impl UiComponent for Player {
@@ -42,7 +134,7 @@ impl UiComponent for Player {
 
     fn render(&self, frame: &mut Frame, area: Rect) {
         let playing = self.playlist.as_ref().unwrap().borrow().playing();
-        let duration = playing.and_then(|x| x.get_duration().ok());
+        let duration = playing.as_ref().and_then(|x| x.get_duration().ok());
         let total_duration_secs = duration.map(|x| x.as_secs());
         let progress_duration_secs = self
             .playlist
@@ -52,6 +144,15 @@ impl UiComponent for Player {
             .progress_hundred_ms
             .load(Ordering::Acquire)
             / 10;
+        let sleep = match self.playlist.as_ref().unwrap().borrow().sleep_timer() {
+            SleepTimer::Off => None,
+            _ => Some(
+                match self.playlist.as_ref().unwrap().borrow().sleep_remaining() {
+                    Some(x) => format!("zZ{:02}:{:02}", x.as_secs() / 60, x.as_secs() % 60),
+                    None => "zZ--:--".to_owned(),
+                },
+            ),
+        };
 
         let block = ratatui::widgets::Block::new()
             .borders(ratatui::widgets::Borders::all())
@@ -63,24 +164,51 @@ impl UiComponent for Player {
             } else {
                 Color::Reset
             }));
+        let block = block.title(
+            Title::from(format!(
+                "vol{:3}%",
+                self.playlist.as_ref().unwrap().borrow().volume_percent()
+            ))
+            .alignment(Alignment::Left)
+            .position(Position::Bottom),
+        );
+        let block = match sleep {
+            Some(x) => block.title(
+                Title::from(x)
+                    .alignment(Alignment::Right)
+                    .position(Position::Bottom),
+            ),
+            None => block,
+        };
         let layout = Layout::default()
             .direction(Direction::Vertical)
-            .constraints(vec![Constraint::Length(1), Constraint::Length(1)])
+            .constraints(vec![
+                Constraint::Length(1),
+                Constraint::Length(1),
+                Constraint::Length(1),
+                Constraint::Length(if SHOW_LEVEL_METERS { 2 } else { 0 }),
+            ])
             .split(block.inner(area));
         frame.render_widget(block, area);
 
+        let (loop_a, loop_b) = self.playlist.as_ref().unwrap().borrow().loop_points();
         let progress_bar = match total_duration_secs {
             Some(0) | None => Cow::Borrowed("[||||||||||||||||||||||||||]"),
             Some(d) => {
                 let percent = (progress_duration_secs as f64) / (d as f64);
                 let percent = percent * 26f64;
                 let percent = percent.round() as usize;
-                Cow::Owned(
-                    "[".to_owned()
-                        + (0..percent).map(|_| '=').collect::<String>().as_str()
-                        + (0..26 - percent).map(|_| ' ').collect::<String>().as_str()
-                        + "]",
-                )
+                let mut bar = (0..percent)
+                    .map(|_| '=')
+                    .chain((0..26 - percent).map(|_| ' '))
+                    .collect::<Vec<_>>();
+                for (marker, point) in [('A', loop_a), ('B', loop_b)] {
+                    if let Some(point) = point {
+                        let position = ((point as f64) / 10f64 / (d as f64) * 26f64) as usize;
+                        bar[position.min(25)] = marker;
+                    }
+                }
+                Cow::Owned("[".to_owned() + bar.iter().collect::<String>().as_str() + "]")
             }
         };
         let progress_bar = Paragraph::new(progress_bar).alignment(Alignment::Center);
@@ -94,7 +222,25 @@ impl UiComponent for Player {
             }
         ))
         .alignment(Alignment::Center);
+        let now_playing = Paragraph::new(
+            match self.playlist.as_ref().unwrap().borrow().current_chapter() {
+                Some((_, chapter)) => chapter.title,
+                None => playing
+                    .as_ref()
+                    .and_then(|x| x.get_title().ok())
+                    .unwrap_or("")
+                    .to_owned(),
+            },
+        )
+        .alignment(Alignment::Center);
         frame.render_widget(progress_bar, layout[0]);
         frame.render_widget(progress, layout[1]);
+        frame.render_widget(now_playing, layout[2]);
+        if SHOW_LEVEL_METERS {
+            frame.render_widget(
+                Paragraph::new(self.level_meters(layout[3].width)),
+                layout[3],
+            );
+        }
     }
 }
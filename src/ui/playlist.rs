@@ -1,4 +1,14 @@
+use crate::chapters::Chapter;
+use crate::config::{
+    scrobbler_log, PLAY_THRESHOLD_PERCENT, PLAY_THRESHOLD_SECS, RESUME_THRESHOLD_SECS,
+    SLEEP_TIMER_FADE_SECS, SLEEP_TIMER_MINUTES,
+};
+use crate::listenbrainz::Scrobbler;
+use crate::resume::ResumePositions;
+use crate::session::Session;
 use crate::song::{Song, SourceItem};
+use crate::stats::{now, Stats};
+use crate::tap::{Samples, Tap};
 use crate::ui::{Library, PlaylistPlaying, ScrollStatus, UiComponent, UiEvent, UiEventResult};
 use ratatui::prelude::*;
 use ratatui::widgets::Row;
@@ -7,11 +17,34 @@ use rodio::OutputStreamHandle;
 use std::cell::RefCell;
 use std::rc::Rc;
 use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
-use std::sync::Arc;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
 use uuid::Uuid;
 
+const UNSET: u64 = u64::MAX;
+
+/// How far into a song, in hundreds of milliseconds, it counts as played: half of it, or a few
+/// minutes of a long one.
+fn play_threshold(song: &Song) -> u64 {
+    let threshold = PLAY_THRESHOLD_SECS * 10;
+    match song.get_duration().map(|x| x.as_millis() as u64 / 100) {
+        Ok(x) if x > 0 => (x * PLAY_THRESHOLD_PERCENT / 100).min(threshold),
+        _ => threshold,
+    }
+}
+
+pub enum SleepTimer {
+    Off,
+    After { minutes: u64, deadline: Instant },
+    EndOfSong,
+    EndOfAlbum,
+}
+
 pub struct Playlist {
     library: Option<Rc<RefCell<Library>>>,
+    resume: Option<Rc<RefCell<ResumePositions>>>,
+    stats: Option<Rc<RefCell<Stats>>>,
+    scrobbler: Option<Scrobbler>,
     items: Vec<Uuid>,
     //ui
     focused: bool,
@@ -22,7 +55,30 @@ pub struct Playlist {
     finished: Arc<AtomicBool>,
     paused: Arc<AtomicBool>,
     should_skip: Arc<AtomicBool>,
+    skipped: bool,
+    /// Whether the playing song was played long enough to count as played.
+    counted: bool,
+    /// When the playing song started playing, in seconds since the Unix epoch.
+    started: Option<u64>,
     pub progress_hundred_ms: Arc<AtomicU64>,
+    /// How many songs of the playlist have begun playing, i.e. set `progress_hundred_ms` to
+    /// where they start.
+    songs_begun: Arc<AtomicU64>,
+    /// The latest samples played, e.g. for visualizing them.
+    pub samples: Arc<Mutex<Samples>>,
+    loop_a: Arc<AtomicU64>,
+    loop_b: Arc<AtomicU64>,
+    /// The song the loop points were set on, counted like `songs_begun`: the next song clears
+    /// them as it begins, before the UI notices that the previous one finished.
+    loop_song: Arc<AtomicU64>,
+    seek_to: Arc<AtomicU64>,
+    sleep_timer: SleepTimer,
+    /// Whether the next song pauses before its first sample, set while the sleep timer is to
+    /// pause playback at the end of the playing song: by the time the UI sees that it finished,
+    /// the next one has already started.
+    sleep_before_next: Arc<AtomicBool>,
+    fade_percent: Arc<AtomicU64>,
+    volume_percent: Arc<AtomicU64>,
 }
 
 impl Playlist {
@@ -31,6 +87,9 @@ impl Playlist {
         stream.play_raw(o)?;
         Ok(Self {
             library: None,
+            resume: None,
+            stats: None,
+            scrobbler: None,
             items: Vec::new(),
             focused: false,
             viewpoint: RefCell::new(ScrollStatus {
@@ -43,12 +102,40 @@ impl Playlist {
             finished: Arc::new(AtomicBool::new(false)),
             paused: Arc::new(AtomicBool::new(false)),
             should_skip: Arc::new(AtomicBool::new(false)),
+            skipped: false,
+            counted: false,
+            started: None,
             progress_hundred_ms: Arc::new(AtomicU64::new(0)),
+            songs_begun: Arc::new(AtomicU64::new(0)),
+            samples: Arc::new(Mutex::new(Samples::new())),
+            loop_a: Arc::new(AtomicU64::new(UNSET)),
+            loop_b: Arc::new(AtomicU64::new(UNSET)),
+            loop_song: Arc::new(AtomicU64::new(0)),
+            seek_to: Arc::new(AtomicU64::new(UNSET)),
+            sleep_timer: SleepTimer::Off,
+            sleep_before_next: Arc::new(AtomicBool::new(false)),
+            fade_percent: Arc::new(AtomicU64::new(100)),
+            volume_percent: Arc::new(AtomicU64::new(100)),
         })
     }
     pub fn set_ref_to_library(&mut self, library: Rc<RefCell<Library>>) {
         self.library = Some(library);
     }
+    pub fn set_ref_to_resume(&mut self, resume: Rc<RefCell<ResumePositions>>) {
+        self.resume = Some(resume);
+    }
+    pub fn set_ref_to_stats(&mut self, stats: Rc<RefCell<Stats>>) {
+        self.stats = Some(stats);
+    }
+    pub fn set_scrobbler(&mut self, scrobbler: Scrobbler) {
+        self.scrobbler = Some(scrobbler);
+    }
+    fn record_play(&self, song: &Song) {
+        let _ = self.stats.as_ref().unwrap().borrow_mut().record_play(song);
+        if let Some(scrobbler) = &self.scrobbler {
+            scrobbler.listen(song, self.started.unwrap_or_else(now));
+        }
+    }
     fn next_item(&mut self) {
         self.viewpoint.borrow_mut().steps += 1;
     }
@@ -73,14 +160,305 @@ impl Playlist {
             None
         }
     }
+    pub fn selected_song(&self) -> Option<Song> {
+        self.song_at(self.viewpoint.borrow().selected)
+    }
+    pub fn songs(&self) -> Vec<Song> {
+        (0..self.items.len())
+            .filter_map(|i| self.song_at(i))
+            .collect()
+    }
+    /// The ids of `songs`, in the same order.
+    pub fn song_ids(&self) -> Vec<Uuid> {
+        (0..self.items.len())
+            .filter(|i| self.song_at(*i).is_some())
+            .map(|i| self.items[i])
+            .collect()
+    }
     pub fn pause_or_resume(&mut self) {
         self.paused.fetch_xor(true, Ordering::SeqCst);
     }
+    pub fn volume_percent(&self) -> u64 {
+        self.volume_percent.load(Ordering::Acquire)
+    }
+    pub fn volume_up(&mut self) {
+        let volume = self.volume_percent.load(Ordering::Acquire);
+        self.volume_percent
+            .store((volume + 5).min(100), Ordering::Release);
+    }
+    pub fn volume_down(&mut self) {
+        let volume = self.volume_percent.load(Ordering::Acquire);
+        self.volume_percent
+            .store(volume.saturating_sub(5), Ordering::Release);
+    }
     pub fn next_song(&mut self) {
+        if let PlaylistPlaying::Index(_) = self.playing {
+            self.skipped = true;
+        }
         self.should_skip.store(true, Ordering::SeqCst);
     }
-    pub fn play_song(&mut self, id: Uuid) {
+    /// Sets the A point of the A-B loop to the current position, clearing any B point.
+    pub fn set_loop_a(&mut self) {
+        if let PlaylistPlaying::Index(i) = self.playing {
+            self.loop_song.store(i as u64 + 1, Ordering::Release);
+            self.loop_b.store(UNSET, Ordering::Release);
+            self.loop_a.store(
+                self.progress_hundred_ms.load(Ordering::Acquire),
+                Ordering::Release,
+            );
+        }
+    }
+    /// Sets the B point of the A-B loop to the current position, which starts looping.
+    pub fn set_loop_b(&mut self) {
+        let a = self.loop_a.load(Ordering::Acquire);
+        let progress = self.progress_hundred_ms.load(Ordering::Acquire);
+        if a != UNSET && progress > a {
+            self.loop_b.store(progress, Ordering::Release);
+        }
+    }
+    pub fn clear_loop(&mut self) {
+        self.loop_a.store(UNSET, Ordering::Release);
+        self.loop_b.store(UNSET, Ordering::Release);
+    }
+    /// Returns the A and B points of the A-B loop in hundreds of milliseconds.
+    pub fn loop_points(&self) -> (Option<u64>, Option<u64>) {
+        let a = self.loop_a.load(Ordering::Acquire);
+        let b = self.loop_b.load(Ordering::Acquire);
+        ((a != UNSET).then_some(a), (b != UNSET).then_some(b))
+    }
+    fn song_at(&self, i: usize) -> Option<Song> {
+        match self
+            .library
+            .as_ref()
+            .unwrap()
+            .borrow()
+            .find_by_id(self.items.get(i)?)
+        {
+            Some(SourceItem::Song(_, x)) => Some((*x).clone()),
+            _ => None,
+        }
+    }
+    /// Switches to the next sleep timer setting: the durations in `SLEEP_TIMER_MINUTES`,
+    /// then the end of the current song, then the end of the current album, then off.
+    pub fn cycle_sleep_timer(&mut self) {
+        let next_minutes = |current: u64| SLEEP_TIMER_MINUTES.iter().find(|x| **x > current);
+        self.sleep_timer = match self.sleep_timer {
+            SleepTimer::Off => {
+                next_minutes(0).map_or(SleepTimer::EndOfSong, |x| SleepTimer::After {
+                    minutes: *x,
+                    deadline: Instant::now() + Duration::from_secs(x * 60),
+                })
+            }
+            SleepTimer::After { minutes, .. } => {
+                next_minutes(minutes).map_or(SleepTimer::EndOfSong, |x| SleepTimer::After {
+                    minutes: *x,
+                    deadline: Instant::now() + Duration::from_secs(x * 60),
+                })
+            }
+            SleepTimer::EndOfSong => SleepTimer::EndOfAlbum,
+            SleepTimer::EndOfAlbum => SleepTimer::Off,
+        };
+        self.fade_percent.store(100, Ordering::Release);
+        self.update_sleep_before_next();
+    }
+    pub fn sleep_timer(&self) -> &SleepTimer {
+        &self.sleep_timer
+    }
+    /// Returns the time left before the sleep timer pauses playback, if it is known.
+    pub fn sleep_remaining(&self) -> Option<Duration> {
+        let song_remaining = |i: usize| {
+            let duration = self.song_at(i)?.get_duration().ok()?;
+            let progress =
+                Duration::from_millis(self.progress_hundred_ms.load(Ordering::Acquire) * 100);
+            (!duration.is_zero()).then(|| duration.saturating_sub(progress))
+        };
+        match (&self.sleep_timer, &self.playing) {
+            (SleepTimer::Off, _) => None,
+            (SleepTimer::After { deadline, .. }, _) => {
+                Some(deadline.saturating_duration_since(Instant::now()))
+            }
+            (SleepTimer::EndOfSong, PlaylistPlaying::Index(i)) => song_remaining(*i),
+            (SleepTimer::EndOfAlbum, PlaylistPlaying::Index(i)) => {
+                let mut remaining = song_remaining(*i)?;
+                let mut j = *i + 1;
+                while j < self.items.len() && self.same_album(*i, j) {
+                    remaining += self.song_at(j)?.get_duration().ok()?;
+                    j += 1;
+                }
+                Some(remaining)
+            }
+            (_, _) => None,
+        }
+    }
+    fn same_album(&self, i: usize, j: usize) -> bool {
+        match (self.song_at(i), self.song_at(j)) {
+            (Some(x), Some(y)) => match (x.get_album(), y.get_album()) {
+                (Ok(x), Ok(y)) => !x.is_empty() && x == y,
+                _ => false,
+            },
+            _ => false,
+        }
+    }
+    fn fall_asleep(&mut self) {
+        self.paused.store(true, Ordering::Release);
+        self.fade_percent.store(100, Ordering::Release);
+        self.sleep_timer = SleepTimer::Off;
+        self.update_sleep_before_next();
+    }
+    fn update_sleep_before_next(&self) {
+        let sleep = match (&self.sleep_timer, &self.playing) {
+            (SleepTimer::EndOfSong, PlaylistPlaying::Index(_)) => true,
+            (SleepTimer::EndOfAlbum, PlaylistPlaying::Index(i)) => !self.same_album(*i, i + 1),
+            _ => false,
+        };
+        self.sleep_before_next.store(sleep, Ordering::Release);
+    }
+    pub fn save_session(&self, session: &mut Session) {
+        let library = self.library.as_ref().unwrap().borrow();
+        session.queue = self
+            .items
+            .iter()
+            .map(|x| match library.find_by_id(x).unwrap() {
+                SourceItem::Song(_, s) => s.key(),
+                SourceItem::Title(..) => unreachable!(),
+            })
+            .collect();
+        session.playing = match self.playing {
+            PlaylistPlaying::Index(i) => Some(i),
+            _ => None,
+        };
+        session.progress_hundred_ms = self.progress_hundred_ms.load(Ordering::Acquire);
+        session.paused = self.paused.load(Ordering::Acquire);
+        session.volume_percent = self.volume_percent.load(Ordering::Acquire);
+        session.loop_points = self.loop_points();
+    }
+    /// Refills the playlist from a saved session. Songs that are no longer in the library are
+    /// dropped, and if the playing one is among them playback continues from the next song.
+    /// Returns the first error of the songs that can't be played anymore, which are dropped too.
+    pub fn restore_session(&mut self, session: &Session) -> anyhow::Result<()> {
+        let mut history = Vec::new();
+        let mut upcoming = Vec::new();
+        let mut resumed = session.playing.is_some();
+        for (i, key) in session.queue.iter().enumerate() {
+            let id = self.library.as_ref().unwrap().borrow().find_by_key(key);
+            match (id, session.playing) {
+                (Some(id), Some(playing)) if i >= playing => upcoming.push(id),
+                (Some(id), _) => history.push(id),
+                (None, Some(playing)) if i == playing => resumed = false,
+                (None, _) => {}
+            }
+        }
+        self.paused.store(session.paused, Ordering::Release);
+        self.volume_percent
+            .store(session.volume_percent.min(100), Ordering::Release);
+        self.playing = if history.is_empty() {
+            PlaylistPlaying::None
+        } else {
+            PlaylistPlaying::Done
+        };
+        self.items = history;
+        let resumed_song = self.items.len() as u64 + 1;
+        // The playing song may already have been counted as played before
+        self.counted = resumed
+            && upcoming.first().is_some_and(|x| {
+                self.library
+                    .as_ref()
+                    .unwrap()
+                    .borrow()
+                    .find_by_id(x)
+                    .is_some_and(|x| {
+                        matches!(x, SourceItem::Song(_, s)
+                        if session.progress_hundred_ms >= play_threshold(s))
+                    })
+            });
+        let mut result = Ok(());
+        for (i, id) in upcoming.into_iter().enumerate() {
+            let start = (i == 0 && resumed).then_some(session.progress_hundred_ms);
+            if let Err(e) = self.enqueue(id, start) {
+                if i == 0 {
+                    resumed = false;
+                    self.counted = false;
+                }
+                if result.is_ok() {
+                    result = Err(e);
+                }
+            }
+        }
+        // The loop points are the playing song's, which may have been dropped
+        if let (true, (Some(a), b)) = (resumed, session.loop_points) {
+            self.loop_song.store(resumed_song, Ordering::Release);
+            self.loop_a.store(a, Ordering::Release);
+            self.loop_b.store(b.unwrap_or(UNSET), Ordering::Release);
+        }
+        result
+    }
+    pub fn play_song(&mut self, id: Uuid) -> anyhow::Result<()> {
+        self.enqueue(id, None)
+    }
+    /// Adds a song to the playlist, starting at `start` instead of its beginning.
+    pub fn play_song_at(&mut self, id: Uuid, start: Duration) -> anyhow::Result<()> {
+        self.enqueue(id, Some(start.as_millis() as u64 / 100))
+    }
+    /// Seeks the playing song to `position`.
+    pub fn seek(&mut self, position: Duration) {
+        if let PlaylistPlaying::Index(_) = self.playing {
+            self.seek_to
+                .store(position.as_millis() as u64 / 100, Ordering::Release);
+        }
+    }
+    /// Returns the index and the chapter of the playing song that is being played.
+    pub fn current_chapter(&self) -> Option<(usize, Chapter)> {
+        let PlaylistPlaying::Index(i) = self.playing else {
+            return None;
+        };
+        let song = self.song_at(i)?;
+        let progress =
+            Duration::from_millis(self.progress_hundred_ms.load(Ordering::Acquire) * 100);
+        let chapters = song.get_chapters().ok()?;
+        let current = chapters.iter().rposition(|x| x.start <= progress)?;
+        Some((current, chapters[current].clone()))
+    }
+    pub fn next_chapter(&mut self) {
+        let Some(song) = self.playing() else {
+            return;
+        };
+        let progress =
+            Duration::from_millis(self.progress_hundred_ms.load(Ordering::Acquire) * 100);
+        if let Some(x) = song
+            .get_chapters()
+            .unwrap_or_default()
+            .iter()
+            .find(|x| x.start > progress)
+        {
+            self.seek(x.start);
+        }
+    }
+    /// Seeks to the start of the current chapter, or to the previous chapter if the current one
+    /// has only just started.
+    pub fn prev_chapter(&mut self) {
+        let (Some(song), Some((i, current))) = (self.playing(), self.current_chapter()) else {
+            return;
+        };
+        let progress =
+            Duration::from_millis(self.progress_hundred_ms.load(Ordering::Acquire) * 100);
+        if progress.saturating_sub(current.start) > Duration::from_secs(3) || i == 0 {
+            self.seek(current.start);
+        } else {
+            self.seek(song.get_chapters().unwrap_or_default()[i - 1].start);
+        }
+    }
+    /// Appends a song to the playlist and the playback queue. It starts at `start` hundreds of
+    /// milliseconds, or at its resume position if `start` is `None`. A song that can't be
+    /// decoded, e.g. as its file is missing, is left out.
+    fn enqueue(&mut self, id: Uuid, start: Option<u64>) -> anyhow::Result<()> {
         use rodio::Source;
+        let song = match self.library.as_ref().unwrap().borrow().find_by_id(&id) {
+            Some(SourceItem::Song(_, x)) => x.clone(),
+            _ => unreachable!(),
+        };
+        let decoded = song
+            .decode()
+            .map_err(|e| e.context(song.path().to_owned()))?;
         self.items.push(id);
         match self.playing {
             PlaylistPlaying::None => {
@@ -91,44 +469,94 @@ impl Playlist {
             }
             PlaylistPlaying::Index(_) => {}
         }
-        let sync_signal = self.queue_tx.append_with_signal(
-            if let SourceItem::Song(_, x) = self
-                .library
-                .as_ref()
-                .unwrap()
-                .borrow()
-                .find_by_id(&id)
-                .unwrap()
-            {
-                let finished = Arc::clone(&self.finished);
-                let paused = self.paused.clone();
-                let should_skip = self.should_skip.clone();
-                let progress_hundred_ms = self.progress_hundred_ms.clone();
-                x.decode()
+        let sync_signal = self.queue_tx.append_with_signal({
+            let finished = Arc::clone(&self.finished);
+            let paused = self.paused.clone();
+            let sleep_before_next = self.sleep_before_next.clone();
+            let should_skip = self.should_skip.clone();
+            let progress_hundred_ms = self.progress_hundred_ms.clone();
+            let songs_begun = self.songs_begun.clone();
+            let position = self.items.len() as u64;
+            let loop_a = self.loop_a.clone();
+            let loop_b = self.loop_b.clone();
+            let loop_song = self.loop_song.clone();
+            let seek_to = self.seek_to.clone();
+            let fade_percent = self.fade_percent.clone();
+            let volume_percent = self.volume_percent.clone();
+            let mut start = Some(start.unwrap_or_else(|| {
+                self.resume
+                    .as_ref()
                     .unwrap()
-                    .pausable(false)
-                    .skippable()
-                    .periodic_access(std::time::Duration::from_millis(100), move |x| {
-                        if should_skip.load(Ordering::Acquire) {
-                            x.skip();
-                            finished.store(true, Ordering::Release);
-                            should_skip.store(false, Ordering::Release);
-                            return;
+                    .borrow()
+                    .get(&song.key())
+                    .unwrap_or(0)
+            }));
+            let source = decoded
+                .amplify(1f32)
+                .pausable(false)
+                .skippable()
+                .periodic_access(std::time::Duration::from_millis(100), move |x| {
+                    if should_skip.load(Ordering::Acquire) {
+                        x.skip();
+                        finished.store(true, Ordering::Release);
+                        should_skip.store(false, Ordering::Release);
+                        return;
+                    }
+                    if start.is_some() && sleep_before_next.swap(false, Ordering::AcqRel) {
+                        paused.store(true, Ordering::Release);
+                    }
+                    x.inner_mut().set_paused(paused.load(Ordering::Acquire));
+                    x.inner_mut().inner_mut().set_factor(
+                        (volume_percent.load(Ordering::Acquire)
+                            * fade_percent.load(Ordering::Acquire)) as f32
+                            / 10000f32,
+                    );
+                    if let Some(start) = start.take() {
+                        songs_begun.store(position, Ordering::Release);
+                        if loop_song.load(Ordering::Acquire) != position {
+                            loop_a.store(UNSET, Ordering::Release);
+                            loop_b.store(UNSET, Ordering::Release);
                         }
-                        x.inner_mut().set_paused(paused.load(Ordering::Acquire));
-                        if !paused.load(Ordering::Acquire) {
-                            progress_hundred_ms.fetch_add(1, Ordering::Release);
+                        let start = if start != 0
+                            && x.try_seek(std::time::Duration::from_millis(start * 100))
+                                .is_ok()
+                        {
+                            start
+                        } else {
+                            0
+                        };
+                        progress_hundred_ms.store(start, Ordering::Release);
+                        return;
+                    }
+                    let seek = seek_to.swap(UNSET, Ordering::AcqRel);
+                    if seek != UNSET
+                        && x.try_seek(std::time::Duration::from_millis(seek * 100))
+                            .is_ok()
+                    {
+                        progress_hundred_ms.store(seek, Ordering::Release);
+                        return;
+                    }
+                    if !paused.load(Ordering::Acquire) {
+                        progress_hundred_ms.fetch_add(1, Ordering::Release);
+                    }
+                    let b = loop_b.load(Ordering::Acquire);
+                    if b != UNSET && progress_hundred_ms.load(Ordering::Acquire) >= b {
+                        let a = loop_a.load(Ordering::Acquire);
+                        if x.try_seek(std::time::Duration::from_millis(a * 100))
+                            .is_ok()
+                        {
+                            progress_hundred_ms.store(a, Ordering::Release);
                         }
-                    })
-            } else {
-                unreachable!()
-            },
-        );
+                    }
+                });
+            Tap::new(source, Arc::clone(&self.samples))
+        });
         let finished = Arc::clone(&self.finished);
         tokio::task::spawn_blocking(move || {
             sync_signal.recv().unwrap();
             finished.store(true, Ordering::Release);
         });
+        Ok(())
     }
 }
 
@@ -157,15 +585,93 @@ impl UiComponent for Playlist {
             }
             UiEvent::Tick => {
                 if self.finished.load(Ordering::Acquire) {
-                    self.progress_hundred_ms.store(0, Ordering::Release);
+                    if let PlaylistPlaying::Index(i) = self.playing {
+                        match self.sleep_timer {
+                            SleepTimer::EndOfSong => self.fall_asleep(),
+                            SleepTimer::EndOfAlbum if !self.same_album(i, i + 1) => {
+                                self.fall_asleep()
+                            }
+                            _ => {}
+                        }
+                        if let Some(x) = self.song_at(i) {
+                            let mut resume = self.resume.as_ref().unwrap().borrow_mut();
+                            if !self.skipped {
+                                resume.remove(&x.key());
+                            }
+                            let _ = resume.save();
+                            if !self.counted {
+                                if self.skipped {
+                                    let mut stats = self.stats.as_ref().unwrap().borrow_mut();
+                                    let _ = stats.record_skip(&x);
+                                } else {
+                                    self.record_play(&x);
+                                }
+                            }
+                            if let (Some(path), Some(started)) = (scrobbler_log(), self.started) {
+                                let listened = self.counted || !self.skipped;
+                                let _ = crate::scrobbler_log::append(&path, &x, listened, started);
+                            }
+                        }
+                    }
+                    self.skipped = false;
+                    self.counted = false;
+                    self.started = None;
                     self.finished.store(false, Ordering::Release);
                     if let PlaylistPlaying::Index(i) = self.playing {
                         if i + 1 == self.items.len() {
+                            self.progress_hundred_ms.store(0, Ordering::Release);
                             self.playing = PlaylistPlaying::Done;
                         } else {
+                            // The progress is still the finished song's, unless the next one
+                            // began already and set it to where it starts, which it may do
+                            // concurrently, hence only resetting the value loaded beforehand
+                            let progress = self.progress_hundred_ms.load(Ordering::Acquire);
+                            if self.songs_begun.load(Ordering::Acquire) <= i as u64 + 1 {
+                                let _ = self.progress_hundred_ms.compare_exchange(
+                                    progress,
+                                    0,
+                                    Ordering::AcqRel,
+                                    Ordering::Acquire,
+                                );
+                            }
                             self.playing = PlaylistPlaying::Index(i + 1);
                         }
                     }
+                } else if let PlaylistPlaying::Index(i) = self.playing {
+                    if let Some(x) = self.song_at(i) {
+                        let progress = self.progress_hundred_ms.load(Ordering::Acquire);
+                        if self.started.is_none() && progress > 0 {
+                            self.started = Some(now().saturating_sub(progress / 10));
+                            if let Some(scrobbler) = &self.scrobbler {
+                                scrobbler.playing_now(&x);
+                            }
+                        }
+                        if !self.counted && progress > 0 && progress >= play_threshold(&x) {
+                            self.counted = true;
+                            self.record_play(&x);
+                        }
+                        if progress > 0
+                            && x.get_duration().unwrap_or_default()
+                                > Duration::from_secs(RESUME_THRESHOLD_SECS)
+                        {
+                            self.resume
+                                .as_ref()
+                                .unwrap()
+                                .borrow_mut()
+                                .set(x.key(), progress);
+                        }
+                    }
+                }
+                self.update_sleep_before_next();
+                if let Some(remaining) = self.sleep_remaining() {
+                    if remaining.is_zero() {
+                        self.fall_asleep();
+                    } else {
+                        self.fade_percent.store(
+                            (remaining.as_millis() as u64 / SLEEP_TIMER_FADE_SECS / 10).min(100),
+                            Ordering::Release,
+                        );
+                    }
                 }
                 UiEventResult::PassThrough
             }
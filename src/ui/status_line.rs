@@ -5,7 +5,12 @@ use ratatui::widgets::Paragraph;
 
 pub enum StatusLine {
     NothingButHappy,
+    Message(String),
     Find(String),
+    SavePlaylist(String),
+    ExportPlaylist(String),
+    TagsFromPaths(String),
+    RenameFiles(String),
 }
 
 impl UiComponent for StatusLine {
@@ -15,7 +20,11 @@ impl UiComponent for StatusLine {
             UiEvent::FocusLost => UiEventResult::Handled,
             UiEvent::Tick => UiEventResult::PassThrough,
             UiEvent::Key(crossterm::event::KeyCode::Backspace) => match self {
-                Self::Find(s) => {
+                Self::Find(s)
+                | Self::SavePlaylist(s)
+                | Self::ExportPlaylist(s)
+                | Self::TagsFromPaths(s)
+                | Self::RenameFiles(s) => {
                     if !s.is_empty() {
                         s.pop();
                         UiEventResult::Handled
@@ -26,7 +35,11 @@ impl UiComponent for StatusLine {
                 _ => UiEventResult::PassThrough,
             },
             UiEvent::Key(crossterm::event::KeyCode::Char(c)) => match self {
-                Self::Find(s) => {
+                Self::Find(s)
+                | Self::SavePlaylist(s)
+                | Self::ExportPlaylist(s)
+                | Self::TagsFromPaths(s)
+                | Self::RenameFiles(s) => {
                     s.push(c);
                     UiEventResult::Handled
                 }
@@ -39,7 +52,14 @@ impl UiComponent for StatusLine {
         frame.render_widget(
             match self {
                 Self::NothingButHappy => Paragraph::new("> Life goes on~"),
+                Self::Message(s) => Paragraph::new("> ".to_owned() + s),
                 Self::Find(s) => Paragraph::new("?".to_owned() + s),
+                Self::SavePlaylist(s) => Paragraph::new("Save playlist as: ".to_owned() + s),
+                Self::ExportPlaylist(s) => Paragraph::new("Export playlist to: ".to_owned() + s),
+                Self::TagsFromPaths(s) => {
+                    Paragraph::new("Tags from paths, pattern: ".to_owned() + s)
+                }
+                Self::RenameFiles(s) => Paragraph::new("Rename files, pattern: ".to_owned() + s),
             },
             area,
         );
@@ -0,0 +1,199 @@
+use crate::song::Song;
+use crate::tags::TagField;
+use crate::ui::{UiComponent, UiEvent, UiEventResult};
+use ratatui::prelude::*;
+use ratatui::widgets::{Block, BorderType, Borders, Clear, Paragraph};
+
+/// A popup editing the tags of one or more songs. Fields the songs disagree on are left as they
+/// are unless edited. Changes are previewed before they are saved.
+pub struct TagEditor {
+    songs: Vec<Song>,
+    /// Each field with the value all songs share (`None` if they differ) and what it was changed
+    /// to, if it was.
+    fields: Vec<(TagField, Option<String>, Option<String>)>,
+    selected: usize,
+    /// What is being typed into the selected field.
+    input: Option<String>,
+    /// Whether the changes are shown instead of the fields.
+    preview: bool,
+    scroll: u16,
+}
+
+impl TagEditor {
+    pub fn new(songs: Vec<Song>) -> Self {
+        let fields = TagField::ALL
+            .into_iter()
+            .map(|field| {
+                let mut values = songs.iter().map(|x| field.get(x));
+                let first = values.next().unwrap_or_default();
+                let shared = values.all(|x| x == first).then_some(first);
+                (field, shared, None)
+            })
+            .collect();
+        Self {
+            songs,
+            fields,
+            selected: 0,
+            input: None,
+            preview: false,
+            scroll: 0,
+        }
+    }
+    /// The edited fields, with their new values.
+    fn changes(&self) -> Vec<(TagField, String)> {
+        self.fields
+            .iter()
+            .filter_map(|(field, shared, edited)| match edited {
+                Some(x) if shared.as_ref() != Some(x) => Some((*field, x.clone())),
+                _ => None,
+            })
+            .collect()
+    }
+    /// The paths of the songs being edited.
+    pub fn paths(&self) -> Vec<String> {
+        self.songs.iter().map(|x| x.path().to_owned()).collect()
+    }
+    /// Writes the changes to every song, skipping the ones that can't be. Returns how many songs
+    /// were written, and the errors of the others.
+    pub fn save(&self) -> (usize, Vec<anyhow::Error>) {
+        let changes = self.changes();
+        let mut written = 0;
+        let mut errors = Vec::new();
+        for song in &self.songs {
+            match crate::tags::write(song.path(), &changes) {
+                Ok(()) => written += 1,
+                Err(e) => errors.push(e.context(song.path().to_owned())),
+            }
+        }
+        (written, errors)
+    }
+    fn diff(&self) -> Vec<Line<'_>> {
+        let changes = self.changes();
+        let mut lines = Vec::new();
+        for song in &self.songs {
+            lines.push(Line::styled(song.path(), Style::new().bold()));
+            for (field, new) in &changes {
+                let old = field.get(song);
+                if old != *new {
+                    lines.push(Line::from(vec![
+                        Span::raw(format!("  {}: ", field.name())),
+                        Span::styled(old, Style::new().fg(Color::LightRed)),
+                        Span::raw(" → "),
+                        Span::styled(new.clone(), Style::new().fg(Color::Green)),
+                    ]));
+                }
+            }
+        }
+        lines
+    }
+}
+
+impl UiComponent for TagEditor {
+    fn handle_event(&mut self, event: UiEvent) -> UiEventResult {
+        use crossterm::event::KeyCode as C;
+        let UiEvent::Key(key) = event else {
+            return UiEventResult::PassThrough;
+        };
+        if let Some(input) = &mut self.input {
+            match key {
+                C::Char(c) => input.push(c),
+                C::Backspace => {
+                    input.pop();
+                }
+                C::Enter => self.fields[self.selected].2 = self.input.take(),
+                C::Esc => self.input = None,
+                _ => {}
+            }
+            return UiEventResult::Handled;
+        }
+        match (self.preview, key) {
+            (true, C::Char('j') | C::Down) => self.scroll = self.scroll.saturating_add(1),
+            (true, C::Char('k') | C::Up) => self.scroll = self.scroll.saturating_sub(1),
+            (true, C::Esc) => self.preview = false,
+            (false, C::Char('j') | C::Down) => {
+                self.selected = (self.selected + 1).min(self.fields.len() - 1)
+            }
+            (false, C::Char('k') | C::Up) => self.selected = self.selected.saturating_sub(1),
+            (false, C::Enter) => {
+                let (_, shared, edited) = &self.fields[self.selected];
+                self.input = Some(edited.clone().or(shared.clone()).unwrap_or_default());
+            }
+            (false, C::Char('u')) => self.fields[self.selected].2 = None,
+            (false, C::Char('w')) if !self.changes().is_empty() => {
+                self.preview = true;
+                self.scroll = 0;
+            }
+            // Enter while previewing saves and Esc closes the editor
+            (_, C::Enter | C::Esc) => return UiEventResult::PassThrough,
+            _ => {}
+        }
+        UiEventResult::Handled
+    }
+    /// Draws the popup in the middle of `area`.
+    fn render(&self, frame: &mut Frame, area: Rect) {
+        let width = area.width.min(80);
+        let height = if self.preview {
+            area.height
+        } else {
+            (self.fields.len() as u16 + 4).min(area.height)
+        };
+        let area = Rect {
+            x: area.x + (area.width - width) / 2,
+            y: area.y + (area.height - height) / 2,
+            width,
+            height,
+        };
+        let title = match self.songs.as_slice() {
+            [song] => format!("Tags of {}", song.path().split('/').next_back().unwrap()),
+            songs => format!("Tags of {} songs", songs.len()),
+        };
+        let block = Block::new()
+            .borders(Borders::all())
+            .border_type(BorderType::Rounded)
+            .title(title)
+            .title_alignment(Alignment::Center);
+        let lines = if self.preview {
+            let mut lines = self.diff();
+            lines.push(Line::styled("Enter: save, Esc: back", Style::new().dim()));
+            lines
+        } else {
+            let mut lines = self
+                .fields
+                .iter()
+                .enumerate()
+                .map(|(i, (field, shared, edited))| {
+                    let value = match (i == self.selected, &self.input, edited, shared) {
+                        (true, Some(x), _, _) => Span::raw(format!("{x}▏")),
+                        (_, _, Some(x), _) => {
+                            Span::styled(x.clone(), Style::new().fg(Color::Green))
+                        }
+                        (_, _, None, Some(x)) => Span::raw(x.clone()),
+                        (_, _, None, None) => Span::styled("(various)", Style::new().dim()),
+                    };
+                    let name = Span::styled(
+                        format!("{:>13}: ", field.name()),
+                        if i == self.selected {
+                            Style::new().fg(Color::Blue)
+                        } else {
+                            Style::new()
+                        },
+                    );
+                    Line::from(vec![name, value])
+                })
+                .collect::<Vec<_>>();
+            lines.push(Line::raw(""));
+            lines.push(Line::styled(
+                "Enter: edit, u: undo, w: preview and save, Esc: close",
+                Style::new().dim(),
+            ));
+            lines
+        };
+        frame.render_widget(Clear, area);
+        frame.render_widget(
+            Paragraph::new(lines)
+                .block(block)
+                .scroll((if self.preview { self.scroll } else { 0 }, 0)),
+            area,
+        );
+    }
+}
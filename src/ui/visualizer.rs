@@ -0,0 +1,184 @@
+use crate::ui::{Playlist, UiComponent, UiEvent, UiEventResult};
+use ratatui::prelude::*;
+use ratatui::symbols::Marker;
+use ratatui::widgets::canvas::{Canvas, Points};
+use ratatui::widgets::{Bar, BarChart, BarGroup, Block, BorderType, Borders};
+use rustfft::num_complex::Complex;
+use std::cell::{Cell, RefCell};
+use std::rc::Rc;
+use std::time::Instant;
+
+/// Samples per FFT, about 90ms at 44.1kHz.
+const FFT_SIZE: usize = 4096;
+/// The frequencies shown, in Hz.
+const LOW: f32 = 40.;
+const HIGH: f32 = 16000.;
+/// Levels shown, in dBFS.
+const FLOOR_DB: f32 = -70.;
+/// Samples shown by the oscilloscope, about 45ms at 44.1kHz.
+const SCOPE_SIZE: usize = 2048;
+/// How long falling bars take to drop by about two thirds, in seconds.
+const FALL_SECS: f64 = 0.25;
+
+#[derive(Clone, Copy, PartialEq)]
+enum Mode {
+    Off,
+    Spectrum,
+    Oscilloscope,
+}
+
+/// A spectrum analyzer or an oscilloscope of what is playing.
+pub struct Visualizer {
+    playlist: Option<Rc<RefCell<Playlist>>>,
+    mode: Mode,
+    /// When the bars were last drawn, which they fall according to.
+    rendered: Cell<Option<Instant>>,
+    fft: std::sync::Arc<dyn rustfft::Fft<f32>>,
+    /// The heights of the bars in the last frame, from 0 to 1.
+    bars: RefCell<Vec<f32>>,
+}
+
+impl Visualizer {
+    pub fn new() -> Self {
+        Self {
+            playlist: None,
+            mode: Mode::Off,
+            rendered: Cell::new(None),
+            fft: rustfft::FftPlanner::new().plan_fft_forward(FFT_SIZE),
+            bars: RefCell::new(Vec::new()),
+        }
+    }
+    pub fn set_ref_to_playlist(&mut self, playlist: Rc<RefCell<Playlist>>) {
+        self.playlist = Some(playlist);
+    }
+    pub fn is_shown(&self) -> bool {
+        self.mode != Mode::Off
+    }
+    /// Switches between the spectrum, the oscilloscope and nothing.
+    pub fn cycle_mode(&mut self) {
+        self.mode = match self.mode {
+            Mode::Off => Mode::Spectrum,
+            Mode::Spectrum => Mode::Oscilloscope,
+            Mode::Oscilloscope => Mode::Off,
+        };
+        self.bars.borrow_mut().clear();
+    }
+    /// The latest `n` samples played, mixed down to mono, and their sample rate. Empty if
+    /// nothing is playing.
+    fn samples(&self, n: usize) -> (Vec<f32>, u32) {
+        let playlist = self.playlist.as_ref().unwrap().borrow();
+        if playlist.playing().is_none() {
+            return (Vec::new(), 0);
+        }
+        let samples = playlist.samples.lock().unwrap();
+        (samples.mono(n), samples.sample_rate)
+    }
+    /// The level of `bars` logarithmically spaced bands of the spectrum, from 0 to 1.
+    fn spectrum(&self, bars: usize) -> Vec<f32> {
+        let (samples, sample_rate) = self.samples(FFT_SIZE);
+        if samples.len() < FFT_SIZE || bars == 0 {
+            return vec![0.; bars];
+        }
+        // Hann window
+        let mut buffer = samples
+            .iter()
+            .enumerate()
+            .map(|(i, x)| {
+                let w = 0.5 - 0.5 * (std::f32::consts::TAU * i as f32 / FFT_SIZE as f32).cos();
+                Complex::new(x * w, 0.)
+            })
+            .collect::<Vec<_>>();
+        self.fft.process(&mut buffer);
+        let bin_hz = sample_rate as f32 / FFT_SIZE as f32;
+        let magnitude = |bin: usize| buffer[bin].norm() * 4. / FFT_SIZE as f32;
+        (0..bars)
+            .map(|i| {
+                let hz = |i: usize| LOW * (HIGH / LOW).powf(i as f32 / bars as f32);
+                let from = ((hz(i) / bin_hz) as usize).clamp(1, FFT_SIZE / 2 - 1);
+                let to = ((hz(i + 1) / bin_hz) as usize).clamp(from + 1, FFT_SIZE / 2);
+                let peak = (from..to).map(magnitude).fold(0., f32::max);
+                let db = 20. * peak.max(1e-9).log10();
+                ((db - FLOOR_DB) / -FLOOR_DB).clamp(0., 1.)
+            })
+            .collect()
+    }
+}
+
+impl UiComponent for Visualizer {
+    fn handle_event(&mut self, _event: UiEvent) -> UiEventResult {
+        UiEventResult::PassThrough
+    }
+    fn render(&self, frame: &mut Frame, area: Rect) {
+        let block = Block::new()
+            .borders(Borders::all())
+            .border_type(BorderType::Rounded)
+            .title(match self.mode {
+                Mode::Oscilloscope => "Oscilloscope",
+                _ => "Spectrum",
+            })
+            .title_alignment(Alignment::Center);
+        match self.mode {
+            Mode::Off => {}
+            Mode::Spectrum => {
+                let inner = block.inner(area);
+                let levels = self.spectrum(inner.width as usize / 2);
+                // Bars rise at once and fall smoothly, at the same speed whatever the frame rate
+                // and however late frames are
+                let now = Instant::now();
+                let elapsed = self
+                    .rendered
+                    .replace(Some(now))
+                    .map_or(0., |x| (now - x).as_secs_f64());
+                let fall = (-elapsed / FALL_SECS).exp() as f32;
+                let mut bars = self.bars.borrow_mut();
+                bars.resize(levels.len(), 0.);
+                for (bar, level) in bars.iter_mut().zip(levels) {
+                    *bar = level.max(*bar * fall);
+                }
+                let height = inner.height as f32 * 8.;
+                let group = BarGroup::default().bars(
+                    &bars
+                        .iter()
+                        .map(|x| {
+                            Bar::default()
+                                .value((x * height) as u64)
+                                .text_value(String::new())
+                        })
+                        .collect::<Vec<_>>(),
+                );
+                frame.render_widget(
+                    BarChart::default()
+                        .block(block)
+                        .data(group)
+                        .bar_width(1)
+                        .bar_gap(1)
+                        .bar_style(Style::new().fg(Color::LightRed))
+                        .max(height as u64),
+                    area,
+                );
+            }
+            Mode::Oscilloscope => {
+                let (samples, _) = self.samples(SCOPE_SIZE);
+                let coords = samples
+                    .iter()
+                    .enumerate()
+                    .map(|(i, x)| (i as f64, *x as f64))
+                    .collect::<Vec<_>>();
+                frame.render_widget(
+                    Canvas::default()
+                        .block(block)
+                        .marker(Marker::Braille)
+                        .x_bounds([0., SCOPE_SIZE as f64])
+                        .y_bounds([-1., 1.])
+                        .paint(|ctx| {
+                            ctx.draw(&Points {
+                                coords: &coords,
+                                color: Color::LightRed,
+                            })
+                        }),
+                    area,
+                );
+            }
+        }
+    }
+}
@@ -0,0 +1,112 @@
+use crate::playlists::Entry;
+use quick_xml::escape::escape;
+use quick_xml::events::Event;
+use std::path::Path;
+use std::time::Duration;
+
+/// Reads an XSPF playlist. Relative locations are resolved against the playlist's directory.
+pub fn read(path: &Path) -> anyhow::Result<Vec<Entry>> {
+    let content = std::fs::read_to_string(path)?;
+    let base = path.parent().unwrap_or(Path::new(""));
+    let mut reader = quick_xml::Reader::from_str(&content);
+    reader.trim_text(true);
+    let mut entries = Vec::new();
+    let mut track: Option<Entry> = None;
+    let mut element = Vec::new();
+    loop {
+        match reader.read_event()? {
+            Event::Start(e) => {
+                element = e.local_name().as_ref().to_vec();
+                if element == b"track" {
+                    track = Some(Entry::default());
+                }
+            }
+            Event::End(e) => {
+                if e.local_name().as_ref() == b"track" {
+                    entries.extend(track.take().filter(|x| !x.path.is_empty()));
+                }
+                element.clear();
+            }
+            Event::Text(e) => {
+                let Some(ref mut track) = track else {
+                    continue;
+                };
+                let text = e.unescape()?.into_owned();
+                match element.as_slice() {
+                    b"location" if track.path.is_empty() => {
+                        track.path = crate::playlists::resolve(base, &percent_decode(&text))
+                    }
+                    b"title" => track.title = Some(text),
+                    b"creator" => track.artist = Some(text),
+                    b"duration" => {
+                        track.duration = text.parse().ok().map(Duration::from_millis);
+                    }
+                    _ => {}
+                }
+            }
+            Event::Eof => break,
+            _ => {}
+        }
+    }
+    Ok(entries)
+}
+
+/// Writes an XSPF playlist.
+pub fn write(path: &Path, entries: &[Entry]) -> anyhow::Result<()> {
+    let mut content = concat!(
+        "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n",
+        "<playlist version=\"1\" xmlns=\"http://xspf.org/ns/0/\">\n",
+        "  <trackList>\n"
+    )
+    .to_owned();
+    for entry in entries {
+        content += "    <track>\n";
+        content += &format!(
+            "      <location>file://{}</location>\n",
+            escape(&percent_encode(&entry.path))
+        );
+        if let Some(ref title) = entry.title {
+            content += &format!("      <title>{}</title>\n", escape(title));
+        }
+        if let Some(artist) = entry.artist.as_ref().filter(|x| !x.is_empty()) {
+            content += &format!("      <creator>{}</creator>\n", escape(artist));
+        }
+        if let Some(duration) = entry.duration {
+            content += &format!("      <duration>{}</duration>\n", duration.as_millis());
+        }
+        content += "    </track>\n";
+    }
+    content += "  </trackList>\n</playlist>\n";
+    std::fs::write(path, content)?;
+    Ok(())
+}
+
+fn percent_decode(s: &str) -> String {
+    let bytes = s.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        match (bytes[i], s.get(i + 1..i + 3)) {
+            (b'%', Some(hex)) if u8::from_str_radix(hex, 16).is_ok() => {
+                decoded.push(u8::from_str_radix(hex, 16).unwrap());
+                i += 3;
+            }
+            (x, _) => {
+                decoded.push(x);
+                i += 1;
+            }
+        }
+    }
+    String::from_utf8_lossy(&decoded).into_owned()
+}
+
+fn percent_encode(s: &str) -> String {
+    s.bytes()
+        .map(|x| match x {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'/' | b'-' | b'_' | b'.' | b'~' => {
+                (x as char).to_string()
+            }
+            x => format!("%{x:02X}"),
+        })
+        .collect()
+}